@@ -1,5 +1,9 @@
 use core::time::Duration;
 
+use crate::catalog::UnsupportedEntry;
+use crate::registry::ValidationError;
+use crate::verify::WrittenValue;
+
 #[cfg(feature = "std")]
 type Bytes = std::vec::Vec<u8>;
 
@@ -7,7 +11,8 @@ type Bytes = std::vec::Vec<u8>;
 type Bytes = heapless::Vec<u8, 250>;
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u8)]
 pub enum Coefficients {
     /// 53 — Threshold value of the square root function (>0 if sqrt is used).
@@ -157,7 +162,8 @@ impl core::convert::TryFrom<u8> for Coefficients {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum FunctionCodes {
     ReadCoefficients = 30,
@@ -170,7 +176,30 @@ pub enum FunctionCodes {
     ReadChannelValueFloat = 73,
     ReadChannelValueInteger = 74,
     ZeroCommand = 95,
-    // Comamnd 100 Omitted
+    ReadConfigurationBlock = 100,
+}
+
+impl core::convert::TryFrom<u8> for FunctionCodes {
+    type Error = ();
+    #[inline]
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        use FunctionCodes::*;
+        let out = match v {
+            30 => ReadCoefficients,
+            31 => WriteCoefficients,
+            32 => ReadConfigurations,
+            33 => WriteConfiguration,
+            48 => InitializeAndRealese,
+            66 => WriteAndReadNewDeviceAddress,
+            67 => ReadSerialNumber,
+            73 => ReadChannelValueFloat,
+            74 => ReadChannelValueInteger,
+            95 => ZeroCommand,
+            100 => ReadConfigurationBlock,
+            _ => return Err(()),
+        };
+        Ok(out)
+    }
 }
 
 impl FunctionCodes {
@@ -186,11 +215,31 @@ impl FunctionCodes {
             Self::ReadChannelValueFloat => 9,
             Self::ReadChannelValueInteger => 9,
             Self::ZeroCommand => 5,
+            Self::ReadConfigurationBlock => 9,
+        }
+    }
+
+    /// Valid request lengths on the wire, address and CRC included.
+    pub fn request_lens(&self) -> &'static [usize] {
+        match self {
+            Self::ReadCoefficients => &[5],
+            Self::WriteCoefficients => &[9],
+            Self::ReadConfigurations => &[5],
+            Self::WriteConfiguration => &[6],
+            Self::InitializeAndRealese => &[4],
+            Self::WriteAndReadNewDeviceAddress => &[5, 4],
+            Self::ReadSerialNumber => &[4],
+            Self::ReadChannelValueFloat => &[5],
+            Self::ReadChannelValueInteger => &[5],
+            Self::ZeroCommand => &[5, 9],
+            Self::ReadConfigurationBlock => &[5],
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum ZeroCommands {
     SetZeroP1 = 0,
@@ -207,6 +256,52 @@ pub enum ZeroCommands {
     ResetZeroTOB2 = 13,
 }
 
+impl core::convert::TryFrom<u8> for ZeroCommands {
+    type Error = ();
+    #[inline]
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        use ZeroCommands::*;
+        let out = match v {
+            0 => SetZeroP1,
+            1 => ResetZeroP1,
+            2 => SetZeroP2,
+            3 => ResetZeroP2,
+            6 => SetZeroCH0,
+            7 => ResetZeroCH0,
+            8 => SetZeroT,
+            9 => ResetZeroT,
+            10 => SetZeroTOB1,
+            11 => ResetZeroTOB1,
+            12 => SetZeroTOB2,
+            13 => ResetZeroTOB2,
+            _ => return Err(()),
+        };
+        Ok(out)
+    }
+}
+
+impl ZeroCommands {
+    /// Channel affected by this command.
+    pub fn channel(&self) -> Channels {
+        use ZeroCommands::*;
+        match self {
+            SetZeroP1 | ResetZeroP1 => Channels::P1,
+            SetZeroP2 | ResetZeroP2 => Channels::P2,
+            SetZeroCH0 | ResetZeroCH0 => Channels::CH0,
+            SetZeroT | ResetZeroT => Channels::T,
+            SetZeroTOB1 | ResetZeroTOB1 => Channels::TOB1,
+            SetZeroTOB2 | ResetZeroTOB2 => Channels::TOB2,
+        }
+    }
+
+    /// Whether this is a `SetZero*` rather than a `ResetZero*` command.
+    pub fn is_set(&self) -> bool {
+        (*self as u8).is_multiple_of(2)
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Channels {
@@ -220,6 +315,92 @@ pub enum Channels {
     ConRaw = 11,
 }
 
+impl core::convert::TryFrom<u8> for Channels {
+    type Error = ();
+    #[inline]
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        use Channels::*;
+        let out = match v {
+            0 => CH0,
+            1 => P1,
+            2 => P2,
+            3 => T,
+            4 => TOB1,
+            5 => TOB2,
+            10 => ConTc,
+            11 => ConRaw,
+            _ => return Err(()),
+        };
+        Ok(out)
+    }
+}
+
+impl Channels {
+    /// Offset coefficient the device applies to this channel, if any.
+    pub fn offset_coefficient(&self) -> Option<Coefficients> {
+        match self {
+            Channels::CH0 => Some(Coefficients::OffsetCh0),
+            Channels::P1 => Some(Coefficients::PressureOffsetP1),
+            Channels::P2 => Some(Coefficients::PressureOffsetP2),
+            Channels::T => Some(Coefficients::TemperatureOffsetTorUpperThresSw1),
+            Channels::TOB1 => Some(Coefficients::TemperatureOffsetTOB1),
+            Channels::TOB2 => Some(Coefficients::TemperatureOffsetTOB2),
+            Channels::ConTc | Channels::ConRaw => None,
+        }
+    }
+
+    /// Gain coefficient the device applies to this channel, if any.
+    pub fn gain_coefficient(&self) -> Option<Coefficients> {
+        match self {
+            Channels::CH0 => Some(Coefficients::GainFactorCh0),
+            Channels::P1 => Some(Coefficients::GainFactorP1),
+            Channels::P2 => Some(Coefficients::GainFactorP2),
+            _ => None,
+        }
+    }
+
+    /// F95 command that zeroes this channel, if it can be zeroed.
+    pub fn set_zero_command(&self) -> Option<ZeroCommands> {
+        match self {
+            Channels::CH0 => Some(ZeroCommands::SetZeroCH0),
+            Channels::P1 => Some(ZeroCommands::SetZeroP1),
+            Channels::P2 => Some(ZeroCommands::SetZeroP2),
+            Channels::T => Some(ZeroCommands::SetZeroT),
+            Channels::TOB1 => Some(ZeroCommands::SetZeroTOB1),
+            Channels::TOB2 => Some(ZeroCommands::SetZeroTOB2),
+            Channels::ConTc | Channels::ConRaw => None,
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// Status byte trailing F73/F74 replies; a set bit flags a measurement
+    /// error on that channel.
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    pub struct ChannelStatus: u8 {
+        const CH0 = 1 << 0;
+        const P1 = 1 << 1;
+        const P2 = 1 << 2;
+        const T = 1 << 3;
+        const TOB1 = 1 << 4;
+        const TOB2 = 1 << 5;
+        const _ = !0;
+    }
+}
+
+// The bitflags-generated inner type cannot derive `defmt::Format`.
+#[cfg(feature = "defmt")]
+impl defmt::Format for ChannelStatus {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "ChannelStatus({=u8:#010b})", self.bits());
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
 pub enum ConfigurationCommands {
     CfgPressure = 0,
     CfgTemperature = 1,
@@ -243,6 +424,38 @@ pub enum ConfigurationCommands {
     SDI12Available = 33,
 }
 
+impl core::convert::TryFrom<u8> for ConfigurationCommands {
+    type Error = ();
+    #[inline]
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        use ConfigurationCommands::*;
+        let out = match v {
+            0 => CfgPressure,
+            1 => CfgTemperature,
+            2 => Ch0Config,
+            3 => TempIntervalSeconds,
+            4 => TempComp,
+            7 => Filter,
+            9 => DAC,
+            10 => Uart,
+            11 => FilterFactory,
+            12 => Status,
+            13 => DeviceAddress,
+            14 => Pmode,
+            15 => SPS,
+            20 => SDI12,
+            25 => ModbusInterframeTime9k6,
+            26 => ModbusInterframeTime115k2,
+            28 => ConOn,
+            31 => ConRange,
+            32 => ConTempCompMode,
+            33 => SDI12Available,
+            _ => return Err(()),
+        };
+        Ok(out)
+    }
+}
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
@@ -268,6 +481,154 @@ impl From<u8> for KellerErrors {
     }
 }
 
+impl core::fmt::Display for KellerErrors {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            KellerErrors::NonImplementedFunction => f.write_str("function not implemented (1)"),
+            KellerErrors::InvalidAddress => f.write_str("invalid parameter or address (2)"),
+            KellerErrors::IncorrectMessageLength => f.write_str("incorrect message length (3)"),
+            KellerErrors::ErrorSavingValue => f.write_str("error saving value (4)"),
+            KellerErrors::DeviceNotInitialized => f.write_str("device not initialised (32)"),
+            KellerErrors::Other(code) => write!(f, "error code {code}"),
+        }
+    }
+}
+
+impl From<KellerErrors> for u8 {
+    fn from(error: KellerErrors) -> u8 {
+        match error {
+            KellerErrors::NonImplementedFunction => 1,
+            KellerErrors::InvalidAddress => 2,
+            KellerErrors::IncorrectMessageLength => 3,
+            KellerErrors::ErrorSavingValue => 4,
+            KellerErrors::DeviceNotInitialized => 32,
+            KellerErrors::Other(code) => code,
+        }
+    }
+}
+
+/// Device identity returned by F48 (initialise and release).
+///
+/// The firmware version reads as `class.group-year.week`, e.g. 5.20-12.28.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Identity {
+    pub class: u8,
+    pub group: u8,
+    pub year: u8,
+    pub week: u8,
+    /// Size of the device's receive buffer.
+    pub buffer: u8,
+    /// 1 if the device was powered up or restarted since the last F48.
+    pub status: u8,
+}
+
+impl Identity {
+    pub const PAYLOAD_LEN: usize = 6;
+
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        if payload.len() < Self::PAYLOAD_LEN {
+            return None;
+        }
+        Some(Self {
+            class: payload[0],
+            group: payload[1],
+            year: payload[2],
+            week: payload[3],
+            buffer: payload[4],
+            status: payload[5],
+        })
+    }
+
+    pub fn to_payload(&self) -> [u8; Self::PAYLOAD_LEN] {
+        [
+            self.class,
+            self.group,
+            self.year,
+            self.week,
+            self.buffer,
+            self.status,
+        ]
+    }
+
+    /// Whether the device reported a power-up or restart.
+    pub fn was_restarted(&self) -> bool {
+        self.status & 0x01 != 0
+    }
+}
+
+/// Firmware version, `class.group-year.week`.
+impl core::fmt::Display for Identity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}.{:02}-{:02}.{:02}",
+            self.class, self.group, self.year, self.week
+        )
+    }
+}
+
+/// Factory serial number, as read with F67.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SerialNumber(pub u32);
+
+impl SerialNumber {
+    pub const PAYLOAD_LEN: usize = 4;
+
+    /// Decodes the big-endian F67 payload.
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        let bytes = payload.get(..Self::PAYLOAD_LEN)?;
+        Some(Self(u32::from_be_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3],
+        ])))
+    }
+
+    pub fn to_payload(&self) -> [u8; Self::PAYLOAD_LEN] {
+        self.0.to_be_bytes()
+    }
+}
+
+impl core::fmt::Display for SerialNumber {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<u32> for SerialNumber {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<SerialNumber> for u32 {
+    fn from(value: SerialNumber) -> Self {
+        value.0
+    }
+}
+
+/// Serial number and F48 identity of one device. The serial number alone
+/// identifies the sensor; the identity says what firmware it runs.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceInfo {
+    pub serial_number: SerialNumber,
+    pub identity: Identity,
+}
+
+impl core::fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "S/N {} firmware {}", self.serial_number, self.identity)
+    }
+}
+
 #[allow(async_fn_in_trait)]
 pub trait XLineIO {
     type Error;
@@ -276,6 +637,10 @@ pub trait XLineIO {
     async fn clear_rx(&mut self) -> Result<(), Self::Error> {
         Ok(())
     }
+    /// Block until everything passed to `write_all` has left the transmitter.
+    async fn flush(&mut self, _timeout: Duration) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -287,8 +652,48 @@ pub enum ProtocolError<TE> {
     FrameError(XLineFrameError),
     WrongAddress,
     NonMatchingFunctionCode,
+    /// Rejected before sending: the device's firmware lacks this entry.
+    Unsupported(UnsupportedEntry),
+    /// Rejected before sending: the value breaks the entry's constraints.
+    Validation(ValidationError),
+    /// Rejected before sending: the device's write budget is used up.
+    WriteBudgetExceeded {
+        limit: u32,
+    },
+    /// Write-verify mode read back something other than what was written.
+    VerifyMismatch {
+        expected: WrittenValue,
+        actual: WrittenValue,
+    },
 }
 
+impl<E: core::fmt::Display> core::fmt::Display for ProtocolError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ProtocolError::Transport(e) => write!(f, "transport error: {e}"),
+            ProtocolError::Timeout => f.write_str("timed out waiting for the device"),
+            ProtocolError::EchoMismatch => f.write_str("echo does not match the request"),
+            ProtocolError::FrameError(e) => write!(f, "{e}"),
+            ProtocolError::WrongAddress => f.write_str("reply came from another address"),
+            ProtocolError::NonMatchingFunctionCode => {
+                f.write_str("reply is for a different function code")
+            }
+            ProtocolError::Unsupported(entry) => {
+                write!(f, "{entry} is not supported by this firmware")
+            }
+            ProtocolError::Validation(e) => write!(f, "{e}"),
+            ProtocolError::WriteBudgetExceeded { limit } => {
+                write!(f, "write budget of {limit} writes is used up")
+            }
+            ProtocolError::VerifyMismatch { expected, actual } => {
+                write!(f, "wrote {expected} but read back {actual}")
+            }
+        }
+    }
+}
+
+impl<E: core::fmt::Debug + core::fmt::Display> core::error::Error for ProtocolError<E> {}
+
 impl<E> From<E> for ProtocolError<E> {
     fn from(error: E) -> ProtocolError<E> {
         ProtocolError::Transport(error)
@@ -317,6 +722,42 @@ pub struct XLineFrame {
     pub data: Bytes,
 }
 
+impl XLineFrame {
+    /// Parses a request frame (addr, func, data..., CRC_hi, CRC_lo).
+    pub fn from_buffer(buf: &[u8]) -> Result<Self, XLineFrameError> {
+        if buf.len() < 4 {
+            return Err(XLineFrameError::TooShort);
+        }
+        let data_len = buf.len() - 2;
+        let got_crc = u16::from_be_bytes([buf[data_len], buf[data_len + 1]]);
+        let expected_crc = crc16(&buf[..data_len]);
+        if got_crc != expected_crc {
+            return Err(XLineFrameError::BadCrc {
+                expected: expected_crc,
+                got: got_crc,
+            });
+        }
+        let function_code = FunctionCodes::try_from(buf[1])
+            .map_err(|_| XLineFrameError::UnknownFunction(buf[1]))?;
+
+        #[cfg(feature = "std")]
+        let data: Bytes = buf[2..data_len].to_vec();
+
+        #[cfg(all(not(feature = "std"), feature = "embedded"))]
+        let data: Bytes = {
+            let mut v: Bytes = Bytes::new();
+            let _ = v.extend_from_slice(&buf[2..data_len]);
+            v
+        };
+
+        Ok(Self {
+            address: buf[0],
+            function_code,
+            data,
+        })
+    }
+}
+
 /// Errors that can occur while parsing a response frame from raw bytes.
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -327,6 +768,21 @@ pub enum XLineFrameError {
     DeviceError(KellerErrors),
     /// CRC mismatch: (expected, got)
     BadCrc { expected: u16, got: u16 },
+    /// Function code not known to this crate
+    UnknownFunction(u8),
+}
+
+impl core::fmt::Display for XLineFrameError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            XLineFrameError::TooShort => f.write_str("frame too short"),
+            XLineFrameError::DeviceError(e) => write!(f, "device error: {e}"),
+            XLineFrameError::BadCrc { expected, got } => {
+                write!(f, "bad CRC: expected {expected:#06x}, got {got:#06x}")
+            }
+            XLineFrameError::UnknownFunction(func) => write!(f, "unknown function code {func}"),
+        }
+    }
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -339,6 +795,9 @@ pub struct XLineResponseFrame {
 }
 
 impl XLineResponseFrame {
+    /// Parses a reply frame. The CRC is checked before the function code, so
+    /// a garbled frame is reported as [`XLineFrameError::BadCrc`] rather than
+    /// as whatever exception its corrupted bytes happen to spell.
     pub fn from_buffer(buf: &[u8]) -> Result<Self, XLineFrameError> {
         if buf.len() < 5 {
             return Err(XLineFrameError::TooShort);
@@ -347,10 +806,6 @@ impl XLineResponseFrame {
         let addr = buf[0];
         let func = buf[1];
 
-        if func > 127 {
-            return Err(XLineFrameError::DeviceError(KellerErrors::from(buf[2])));
-        }
-
         let data_len = buf.len() - 2;
         let got_crc = u16::from_be_bytes([buf[data_len], buf[data_len + 1]]);
         let expected_crc = crc16(&buf[..data_len]);
@@ -361,6 +816,10 @@ impl XLineResponseFrame {
                 got: got_crc,
             });
         }
+
+        if func > 127 {
+            return Err(XLineFrameError::DeviceError(KellerErrors::from(buf[2])));
+        }
         #[cfg(feature = "std")]
         let payload: Bytes = buf[2..data_len].to_vec();
 
@@ -384,11 +843,13 @@ impl XLineResponseFrame {
     }
 
     pub fn data_as_u8(&self) -> u8 {
-        return self.payload[0];
+        self.payload[0]
     }
 
+    /// First four payload bytes as a big-endian float; F73 replies carry a
+    /// trailing status byte after the value.
     pub fn data_as_f32(&self) -> f32 {
-        f32_from_be_bytes(&self.payload).unwrap()
+        f32_from_be_bytes(&self.payload[..4]).unwrap()
     }
 }
 
@@ -423,3 +884,63 @@ pub fn crc16(data: &[u8]) -> u16 {
     }
     crc
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{KellerErrors, SerialNumber, XLineFrameError, XLineResponseFrame, crc16_hi_lo};
+
+    fn with_crc(frame: &mut [u8]) {
+        let len = frame.len() - 2;
+        let (hi, lo) = crc16_hi_lo(&frame[..len]);
+        frame[len] = hi;
+        frame[len + 1] = lo;
+    }
+
+    #[test]
+    fn exception_replies_decode_to_the_device_error() {
+        let mut frame = [1, 30 | 0x80, 2, 0, 0];
+        with_crc(&mut frame);
+        assert_eq!(
+            XLineResponseFrame::from_buffer(&frame).err(),
+            Some(XLineFrameError::DeviceError(KellerErrors::InvalidAddress))
+        );
+    }
+
+    #[test]
+    fn garbled_exception_replies_fail_the_crc_check() {
+        let mut frame = [1, 30 | 0x80, 2, 0, 0];
+        with_crc(&mut frame);
+        frame[2] = 32;
+        assert!(matches!(
+            XLineResponseFrame::from_buffer(&frame),
+            Err(XLineFrameError::BadCrc { .. })
+        ));
+    }
+
+    #[test]
+    fn serial_number_decodes_big_endian() {
+        // 0x003D_093B = 61 * 65536 + 9 * 256 + 59.
+        let payload = [0x00, 0x3D, 0x09, 0x3B];
+        assert_eq!(
+            SerialNumber::from_payload(&payload),
+            Some(SerialNumber(4_000_059))
+        );
+        assert_eq!(SerialNumber(4_000_059).to_payload(), payload);
+        assert_eq!(
+            SerialNumber::from_payload(&[0xFF, 0xFF, 0xFF, 0xFE, 0x00]),
+            Some(SerialNumber(4_294_967_294))
+        );
+        assert_eq!(SerialNumber::from_payload(&[0x00, 0x3D, 0x09]), None);
+    }
+
+    #[test]
+    fn serial_number_decodes_from_an_f67_reply() {
+        let mut frame = [1, 67, 0x00, 0x3D, 0x09, 0x3B, 0, 0];
+        with_crc(&mut frame);
+        let reply = XLineResponseFrame::from_buffer(&frame).unwrap();
+        assert_eq!(
+            SerialNumber::from_payload(&reply.payload),
+            Some(SerialNumber(4_000_059))
+        );
+    }
+}
@@ -0,0 +1,599 @@
+//! Field tool for KELLER X-line sensors over a serial port or a TCP gateway.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+use serde_json::{Value, json};
+
+use keller_xline::base::{Channels, Coefficients, ConfigurationCommands, XLineIO, ZeroCommands};
+use keller_xline::profile::{ApplyReport, DeviceProfile, Plan};
+use keller_xline::registry::{self, CoefficientInfo, ConfigurationInfo};
+use keller_xline::serial::{DEFAULT_BAUD_RATE, SerialTransport};
+use keller_xline::snapshot::DeviceSnapshot;
+use keller_xline::tcp::TcpTransport;
+use keller_xline::verify::WriteVerify;
+use keller_xline::{KellerXLine, TRANSPARENT_ADDRESS, Uninitialized};
+
+#[cfg(feature = "tui")]
+mod monitor;
+
+#[derive(Parser)]
+#[command(name = "keller-xline", version, about)]
+struct Cli {
+    #[command(flatten)]
+    link: LinkArgs,
+    /// Serial baud rate
+    #[arg(short, long, default_value_t = DEFAULT_BAUD_RATE, global = true)]
+    baud: u32,
+    /// Device address
+    #[arg(short, long, default_value_t = 1, global = true)]
+    address: u8,
+    /// Per-request timeout in milliseconds
+    #[arg(long, default_value_t = 200, global = true)]
+    timeout_ms: u64,
+    /// Print JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+    /// Read every write back; `--verify=TOL` lets coefficients differ by up
+    /// to TOL
+    #[arg(
+        long,
+        global = true,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "0"
+    )]
+    verify: Option<f32>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// The link flags may go before or after the subcommand, so clap cannot
+/// require one of them; [`open`] does.
+#[derive(Args)]
+#[group(multiple = false)]
+struct LinkArgs {
+    /// Serial port, e.g. /dev/ttyUSB0 or COM3
+    #[arg(short, long, global = true)]
+    port: Option<String>,
+    /// Raw TCP gateway, host:port
+    #[arg(long, global = true)]
+    tcp: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Probe a range of addresses for devices
+    Scan {
+        #[arg(long, default_value_t = 1)]
+        from: u8,
+        #[arg(long, default_value_t = 249)]
+        to: u8,
+    },
+    /// Firmware identity (F48) and serial number
+    Info,
+    /// Read one channel (P1, P2, T, TOB1, TOB2, CH0, ConTc, ConRaw)
+    Read { channel: String },
+    /// Read channels repeatedly
+    Watch {
+        /// Channels to read
+        #[arg(default_values_t = [String::from("P1"), String::from("T")])]
+        channels: Vec<String>,
+        #[arg(long, default_value_t = 1000)]
+        interval_ms: u64,
+        /// Stop after this many rounds
+        #[arg(long)]
+        count: Option<u64>,
+    },
+    /// Read or write a coefficient, by number or name
+    Coef {
+        #[command(subcommand)]
+        op: CoefOp,
+    },
+    /// Read or write a configuration entry, by number or name
+    Config {
+        #[command(subcommand)]
+        op: ConfigOp,
+    },
+    /// Send a zero command, e.g. SetZeroP1 or ResetZeroP1
+    Zero {
+        command: String,
+        /// Value the channel should read after zeroing, instead of 0
+        #[arg(long)]
+        value: Option<f32>,
+    },
+    /// Move the device to a new address
+    SetAddress { new_address: u8 },
+    /// Read every known coefficient and configuration entry
+    Dump,
+    /// Save identity, coefficients and configuration to a JSON file
+    Backup { file: PathBuf },
+    /// Write a backup (possibly of another sensor) back to the device
+    Restore { file: PathBuf },
+    /// List what differs between the device and a backup
+    Diff { file: PathBuf },
+    /// List the writes needed to bring the device in line with a profile
+    Plan {
+        /// Profile file, TOML or JSON
+        profile: PathBuf,
+    },
+    /// Bring the device in line with a profile
+    Apply {
+        /// Profile file, TOML or JSON
+        profile: PathBuf,
+        /// Only report what would be written
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Live full-screen view of every device on the bus
+    #[cfg(feature = "tui")]
+    Monitor {
+        #[arg(long, default_value_t = 1)]
+        from: u8,
+        #[arg(long, default_value_t = 249)]
+        to: u8,
+        #[arg(long, default_value_t = 500)]
+        interval_ms: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum CoefOp {
+    Get {
+        coefficient: String,
+    },
+    Set {
+        coefficient: String,
+        #[arg(allow_negative_numbers = true)]
+        value: f32,
+        /// Write even if the value fails validation (NaN, zero gain, ...)
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigOp {
+    Get {
+        entry: String,
+    },
+    Set {
+        entry: String,
+        value: u8,
+        /// Write even if the entry is read-only or the value out of range
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Whichever transport the command line selected.
+enum Link {
+    Serial(SerialTransport),
+    Tcp(TcpTransport),
+}
+
+impl XLineIO for Link {
+    type Error = io::Error;
+
+    async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> io::Result<()> {
+        match self {
+            Link::Serial(t) => t.write_all(buf, timeout).await,
+            Link::Tcp(t) => t.write_all(buf, timeout).await,
+        }
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<()> {
+        match self {
+            Link::Serial(t) => t.read_exact(buf, timeout).await,
+            Link::Tcp(t) => t.read_exact(buf, timeout).await,
+        }
+    }
+
+    async fn clear_rx(&mut self) -> io::Result<()> {
+        match self {
+            Link::Serial(t) => t.clear_rx().await,
+            Link::Tcp(t) => t.clear_rx().await,
+        }
+    }
+
+    async fn flush(&mut self, timeout: Duration) -> io::Result<()> {
+        match self {
+            Link::Serial(t) => t.flush(timeout).await,
+            Link::Tcp(t) => t.flush(timeout).await,
+        }
+    }
+}
+
+type Device = KellerXLine<Link>;
+type Bus = KellerXLine<Link, Uninitialized>;
+type CliResult<T = ()> = Result<T, Box<dyn std::error::Error>>;
+
+/// Finds an enum value by its number or (case-insensitive) variant name.
+fn lookup<T: TryFrom<u8> + core::fmt::Debug>(what: &str, s: &str) -> CliResult<T> {
+    let found = match s.parse::<u8>() {
+        Ok(id) => T::try_from(id).ok(),
+        Err(_) => (0..=u8::MAX)
+            .filter_map(|id| T::try_from(id).ok())
+            .find(|v| format!("{v:?}").eq_ignore_ascii_case(s)),
+    };
+    found.ok_or_else(|| format!("unknown {what} '{s}'").into())
+}
+
+/// Finds a coefficient by number, variant name or registry name.
+fn coefficient(s: &str) -> CliResult<&'static CoefficientInfo> {
+    let found = match s.parse::<u8>() {
+        Ok(id) => registry::coefficient_by_id(id),
+        Err(_) => registry::coefficient_by_name(s),
+    };
+    found.ok_or_else(|| format!("unknown coefficient '{s}'").into())
+}
+
+/// Finds a configuration entry by number, variant name or registry name.
+fn configuration(s: &str) -> CliResult<&'static ConfigurationInfo> {
+    let found = match s.parse::<u8>() {
+        Ok(id) => registry::configuration_by_id(id),
+        Err(_) => registry::configuration_by_name(s),
+    };
+    found.ok_or_else(|| format!("unknown configuration entry '{s}'").into())
+}
+
+fn all<T: TryFrom<u8>>() -> impl Iterator<Item = T> {
+    (0..=u8::MAX).filter_map(|id| T::try_from(id).ok())
+}
+
+fn print(json: bool, value: Value, text: impl FnOnce() -> String) {
+    if json {
+        println!("{value}");
+    } else {
+        println!("{}", text());
+    }
+}
+
+/// Reads a profile as TOML or JSON, going by the file extension.
+fn load_profile(path: &Path) -> CliResult<DeviceProfile> {
+    let text = std::fs::read_to_string(path)?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => Ok(serde_json::from_str(&text)?),
+        _ => Ok(toml::from_str(&text)?),
+    }
+}
+
+fn plan_text(plan: &Plan) -> String {
+    if plan.is_empty() {
+        return String::from("no changes");
+    }
+    let lines: Vec<_> = plan.changes.iter().map(|c| c.to_string()).collect();
+    lines.join("\n")
+}
+
+fn report_text(report: &ApplyReport) -> String {
+    let mut text = String::new();
+    for change in &report.written {
+        text.push_str(&format!("{change}\n"));
+    }
+    let verb = if report.dry_run {
+        "would write"
+    } else {
+        "wrote"
+    };
+    text.push_str(&format!(
+        "{verb} {}, {} unchanged",
+        report.written.len(),
+        report.unchanged
+    ));
+    if report.unsupported > 0 {
+        text.push_str(&format!(", {} not supported", report.unsupported));
+    }
+    text
+}
+
+async fn open(cli: &Cli) -> CliResult<Bus> {
+    let timeout = Duration::from_millis(cli.timeout_ms);
+    let link = match (&cli.link.port, &cli.link.tcp) {
+        (Some(port), _) => Link::Serial(SerialTransport::open(port, cli.baud)?),
+        (None, Some(addr)) => Link::Tcp(TcpTransport::connect(addr.as_str(), timeout * 10).await?),
+        (None, None) => return Err("need --port or --tcp".into()),
+    };
+    let mut dev = KellerXLine::new(link, timeout, cli.address)?;
+    dev.set_write_verify(match cli.verify {
+        None => WriteVerify::Off,
+        Some(tolerance) if tolerance > 0.0 => WriteVerify::Tolerance(tolerance),
+        Some(_) => WriteVerify::Exact,
+    });
+    Ok(dev)
+}
+
+async fn scan(mut bus: Bus, json: bool, from: u8, to: u8) -> CliResult {
+    let mut found = Vec::new();
+    for address in from..=to {
+        if address == TRANSPARENT_ADDRESS {
+            continue;
+        }
+        bus.set_address(address);
+        let mut dev = match bus.init().await {
+            Ok(dev) => dev,
+            Err(e) => {
+                bus = e.xline;
+                continue;
+            }
+        };
+        let identity = dev.identity();
+        let serial = dev.read_serial_number().await.ok();
+        bus = dev.into_uninitialized();
+        if !json {
+            println!(
+                "{address:>3}  firmware {}  serial {}",
+                identity,
+                serial.map_or_else(|| String::from("?"), |s| s.to_string())
+            );
+        }
+        found.push(json!({ "address": address, "identity": identity, "serial": serial }));
+    }
+    if json {
+        println!("{}", Value::Array(found));
+    } else if found.is_empty() {
+        println!("no devices found between {from} and {to}");
+    }
+    Ok(())
+}
+
+async fn dump(dev: &mut Device, json: bool) -> CliResult {
+    let mut coefficients = serde_json::Map::new();
+    // Only what the firmware implements, when F48 identified it.
+    let coefficients_list: Vec<Coefficients> = match dev.firmware() {
+        Some(firmware) => firmware.coefficients().collect(),
+        None => all().collect(),
+    };
+    let configuration_list: Vec<ConfigurationCommands> = match dev.firmware() {
+        Some(firmware) => firmware.configuration().collect(),
+        None => all().collect(),
+    };
+    for c in coefficients_list {
+        let value = dev.read_coefficent(c).await;
+        if !json {
+            match &value {
+                Ok(v) => println!("coef   {:>3} {:<36} {v}", c as u8, format!("{c:?}")),
+                Err(e) => println!("coef   {:>3} {:<36} ({e})", c as u8, format!("{c:?}")),
+            }
+        }
+        if let Ok(v) = value {
+            coefficients.insert(format!("{c:?}"), json!(v));
+        }
+    }
+    let mut configuration = serde_json::Map::new();
+    for v in configuration_list {
+        let value = dev.read_configuration(v).await;
+        if !json {
+            match &value {
+                Ok(x) => println!("config {:>3} {:<36} {x}", v as u8, format!("{v:?}")),
+                Err(e) => println!("config {:>3} {:<36} ({e})", v as u8, format!("{v:?}")),
+            }
+        }
+        if let Ok(x) = value {
+            configuration.insert(format!("{v:?}"), json!(x));
+        }
+    }
+    if json {
+        println!(
+            "{}",
+            json!({ "coefficients": coefficients, "configuration": configuration })
+        );
+    }
+    Ok(())
+}
+
+async fn run(cli: Cli) -> CliResult {
+    let json = cli.json;
+    let bus = open(&cli).await?;
+    if let Command::Scan { from, to } = cli.command {
+        return scan(bus, json, from, to).await;
+    }
+    #[cfg(feature = "tui")]
+    if let Command::Monitor {
+        from,
+        to,
+        interval_ms,
+    } = cli.command
+    {
+        return monitor::run(bus, from, to, Duration::from_millis(interval_ms)).await;
+    }
+    let mut dev = bus.init().await.map_err(|e| e.error)?;
+    let identity = dev.identity();
+
+    match cli.command {
+        Command::Scan { .. } => unreachable!("handled above"),
+        Command::Info => {
+            let info = dev.device_info().await?;
+            let serial = info.serial_number;
+            print(
+                json,
+                json!({ "address": dev.address(), "identity": info.identity, "serial": serial }),
+                || {
+                    format!(
+                        "address  {}\nfirmware {}\nbuffer   {}\nstatus   {}\nserial   {serial}",
+                        dev.address(),
+                        identity,
+                        identity.buffer,
+                        identity.status
+                    )
+                },
+            );
+        }
+        Command::Read { channel } => {
+            let channel: Channels = lookup("channel", &channel)?;
+            let value = dev.read_channel_value(channel).await?;
+            print(
+                json,
+                json!({ "channel": format!("{channel:?}"), "value": value }),
+                || format!("{channel:?} {value}"),
+            );
+        }
+        Command::Watch {
+            channels,
+            interval_ms,
+            count,
+        } => {
+            let channels = channels
+                .iter()
+                .map(|c| lookup::<Channels>("channel", c))
+                .collect::<CliResult<Vec<_>>>()?;
+            let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms));
+            let mut round = 0u64;
+            while count.is_none_or(|n| round < n) {
+                ticker.tick().await;
+                round += 1;
+                let mut values = serde_json::Map::new();
+                let mut line = String::new();
+                for &channel in &channels {
+                    match dev.read_channel_value(channel).await {
+                        Ok(v) => {
+                            values.insert(format!("{channel:?}"), json!(v));
+                            line.push_str(&format!("{channel:?}={v:<12} "));
+                        }
+                        Err(e) => line.push_str(&format!("{channel:?}=({e}) ")),
+                    }
+                }
+                print(json, Value::Object(values), || line);
+            }
+        }
+        Command::Coef { op } => match op {
+            CoefOp::Get { coefficient: name } => {
+                let info = coefficient(&name)?;
+                let c = info.id;
+                let value = dev.read_coefficent(c).await?;
+                print(
+                    json,
+                    json!({
+                        "coefficient": c as u8,
+                        "name": format!("{c:?}"),
+                        "value": value,
+                        "unit": info.unit,
+                    }),
+                    || {
+                        format!("{} {c:?} {value} {}", c as u8, info.unit)
+                            .trim_end()
+                            .to_owned()
+                    },
+                );
+            }
+            CoefOp::Set {
+                coefficient: name,
+                value,
+                force,
+            } => {
+                let c = coefficient(&name)?.id;
+                if force {
+                    dev.force_write_coefficent(c, value).await?;
+                } else {
+                    dev.write_coefficent(c, value).await?;
+                }
+                print(
+                    json,
+                    json!({ "coefficient": c as u8, "written": value }),
+                    || format!("{} {c:?} <- {value}", c as u8),
+                );
+            }
+        },
+        Command::Config { op } => match op {
+            ConfigOp::Get { entry } => {
+                let v = configuration(&entry)?.id;
+                let value = dev.read_configuration(v).await?;
+                print(
+                    json,
+                    json!({ "configuration": v as u8, "name": format!("{v:?}"), "value": value }),
+                    || format!("{} {v:?} {value}", v as u8),
+                );
+            }
+            ConfigOp::Set {
+                entry,
+                value,
+                force,
+            } => {
+                let v = configuration(&entry)?.id;
+                if force {
+                    dev.force_write_configuration(v, value).await?;
+                } else {
+                    dev.write_configuration(v, value).await?;
+                }
+                print(
+                    json,
+                    json!({ "configuration": v as u8, "written": value }),
+                    || format!("{} {v:?} <- {value}", v as u8),
+                );
+            }
+        },
+        Command::Zero { command, value } => {
+            let z: ZeroCommands = lookup("zero command", &command)?;
+            match value {
+                Some(v) => dev.zero_with_value(z, v).await?,
+                None => dev.zero(z).await?,
+            }
+            print(
+                json,
+                json!({ "zero": format!("{z:?}"), "value": value }),
+                || format!("{z:?} done"),
+            );
+        }
+        Command::SetAddress { new_address } => {
+            let old = dev.address();
+            let now = dev.write_address(new_address).await?;
+            print(json, json!({ "old": old, "new": now }), || {
+                format!("address {old} -> {now}")
+            });
+        }
+        Command::Dump => dump(&mut dev, json).await?,
+        Command::Backup { file } => {
+            let snapshot = dev.snapshot().await?;
+            std::fs::write(&file, serde_json::to_string_pretty(&snapshot)?)?;
+            print(
+                json,
+                json!({ "file": file, "coefficients": snapshot.coefficients.len(),
+                        "configuration": snapshot.configuration.len() }),
+                || {
+                    format!(
+                        "saved {} coefficients and {} configuration entries to {}",
+                        snapshot.coefficients.len(),
+                        snapshot.configuration.len(),
+                        file.display()
+                    )
+                },
+            );
+        }
+        Command::Restore { file } => {
+            let snapshot: DeviceSnapshot = serde_json::from_str(&std::fs::read_to_string(&file)?)?;
+            let report = dev.restore(&snapshot).await?;
+            print(json, json!(report), || report_text(&report));
+        }
+        Command::Diff { file } => {
+            let backup: DeviceSnapshot = serde_json::from_str(&std::fs::read_to_string(&file)?)?;
+            let plan = dev.snapshot().await?.diff(&backup);
+            print(json, json!(plan), || plan_text(&plan));
+        }
+        Command::Plan { profile } => {
+            let plan = dev.plan(&load_profile(&profile)?).await?;
+            print(json, json!(plan), || plan_text(&plan));
+        }
+        Command::Apply { profile, dry_run } => {
+            let plan = dev.plan(&load_profile(&profile)?).await?;
+            let report = dev.apply(&plan, dry_run).await?;
+            print(json, json!(report), || report_text(&report));
+        }
+        #[cfg(feature = "tui")]
+        Command::Monitor { .. } => unreachable!("handled above"),
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,416 @@
+//! Full-screen live view of every device on the bus.
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Sparkline, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+
+use keller_xline::base::{ChannelStatus, Channels, ProtocolError, SerialNumber, ZeroCommands};
+use keller_xline::poll::{LinkStats, poll_channels};
+use keller_xline::{Initialized, TRANSPARENT_ADDRESS};
+
+use crate::{Bus, CliResult, Device};
+
+const CHANNELS: [Channels; 4] = [Channels::P1, Channels::P2, Channels::T, Channels::TOB1];
+const HISTORY: usize = 200;
+
+/// One discovered device and what has been read from it so far.
+struct DeviceView {
+    device: Initialized,
+    serial: Option<SerialNumber>,
+    latest: [Option<f32>; CHANNELS.len()],
+    history: [VecDeque<f32>; CHANNELS.len()],
+    status: Option<ChannelStatus>,
+    stats: LinkStats,
+}
+
+impl DeviceView {
+    fn new(device: Initialized, serial: Option<SerialNumber>) -> Self {
+        Self {
+            device,
+            serial,
+            latest: [None; CHANNELS.len()],
+            history: Default::default(),
+            status: None,
+            stats: LinkStats::default(),
+        }
+    }
+
+    fn address(&self) -> u8 {
+        self.device.address()
+    }
+
+    fn push(&mut self, values: [Option<f32>; CHANNELS.len()]) {
+        self.latest = values;
+        for (history, value) in self.history.iter_mut().zip(values) {
+            if let Some(v) = value {
+                if history.len() == HISTORY {
+                    history.pop_front();
+                }
+                history.push_back(v);
+            }
+        }
+    }
+}
+
+struct Monitor {
+    devices: Vec<DeviceView>,
+    table: TableState,
+    /// Coefficients of the selected device, shown in a popup while `Some`.
+    coefficients: Option<Vec<(String, String)>>,
+    message: String,
+    stats: LinkStats,
+    /// Zero command waiting for `y`; zeroing is persistent, so one stray
+    /// keypress must not send it.
+    pending: Option<(usize, ZeroCommands)>,
+}
+
+impl Monitor {
+    fn selected(&self) -> Option<usize> {
+        self.table.selected().filter(|&i| i < self.devices.len())
+    }
+}
+
+/// Initialises every device that answers in `from..=to`.
+async fn discover(mut bus: Bus, from: u8, to: u8, stats: &mut LinkStats) -> (Bus, Vec<DeviceView>) {
+    let mut found = Vec::new();
+    for address in from..=to {
+        if address == TRANSPARENT_ADDRESS {
+            continue;
+        }
+        bus.set_address(address);
+        let mut dev = match bus.init().await {
+            Ok(dev) => {
+                stats.record(&Ok::<_, ProtocolError<io::Error>>(()));
+                dev
+            }
+            Err(e) => {
+                stats.record(&Err::<(), _>(e.error));
+                bus = e.xline;
+                continue;
+            }
+        };
+        let serial = dev.read_serial_number().await;
+        stats.record(&serial);
+        found.push(DeviceView::new(dev.state(), serial.ok()));
+        bus = dev.into_uninitialized();
+    }
+    (bus, found)
+}
+
+async fn poll_all(dev: &mut Device, monitor: &mut Monitor) {
+    for view in &mut monitor.devices {
+        dev.switch_to(view.device);
+        let mut values = [None; CHANNELS.len()];
+        let mut stats = LinkStats::default();
+        let status = poll_channels(dev, &CHANNELS, &mut values, &mut stats).await;
+        // Picks up a new identity if the device was re-initialised.
+        view.device = dev.state();
+        if status.is_some() {
+            view.status = status;
+        }
+        view.push(values);
+        view.stats.merge(&stats);
+        monitor.stats.merge(&stats);
+    }
+}
+
+fn confirm_zero(monitor: &mut Monitor, command: ZeroCommands) {
+    let Some(i) = monitor.selected() else {
+        return;
+    };
+    monitor.pending = Some((i, command));
+    monitor.message = format!(
+        "send {command:?} to {}? y to confirm, any other key cancels",
+        monitor.devices[i].address()
+    );
+}
+
+async fn zero(dev: &mut Device, monitor: &mut Monitor, i: usize, command: ZeroCommands) {
+    let address = monitor.devices[i].address();
+    dev.switch_to(monitor.devices[i].device);
+    let result = dev.zero(command).await;
+    monitor.stats.record(&result);
+    monitor.message = match result {
+        Ok(()) => format!("{command:?} sent to {address}"),
+        Err(e) => format!("{command:?} on {address} failed: {e}"),
+    };
+}
+
+async fn inspect(dev: &mut Device, monitor: &mut Monitor) {
+    let Some(i) = monitor.selected() else {
+        return;
+    };
+    dev.switch_to(monitor.devices[i].device);
+    let firmware = dev.firmware();
+    let mut rows = Vec::new();
+    for channel in [
+        Channels::CH0,
+        Channels::P1,
+        Channels::P2,
+        Channels::T,
+        Channels::TOB1,
+        Channels::TOB2,
+    ] {
+        // ID 72 is not the T offset on every firmware, so ask the catalog.
+        let coefficients = match firmware {
+            Some(firmware) => [
+                firmware.offset_coefficient(channel),
+                firmware.gain_coefficient(channel),
+            ],
+            None => [channel.offset_coefficient(), channel.gain_coefficient()],
+        };
+        for c in coefficients.into_iter().flatten() {
+            let value = dev.read_coefficent(c).await;
+            monitor.stats.record(&value);
+            let value = match value {
+                Ok(v) => v.to_string(),
+                Err(e) => format!("({e})"),
+            };
+            rows.push((format!("{:>3} {c:?}", c as u8), value));
+        }
+    }
+    monitor.coefficients = Some(rows);
+}
+
+fn sparkline_data(history: &VecDeque<f32>) -> (Vec<u64>, f32, f32) {
+    let min = history.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = history.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let span = max - min;
+    let data = history
+        .iter()
+        .map(|v| {
+            if span > 0.0 {
+                1 + ((v - min) / span * 99.0) as u64
+            } else {
+                50
+            }
+        })
+        .collect();
+    (data, min, max)
+}
+
+fn value_text(value: Option<f32>) -> String {
+    value.map_or_else(|| String::from("-"), |v| format!("{v:.4}"))
+}
+
+fn status_text(status: Option<ChannelStatus>) -> String {
+    match status {
+        None => String::from("?"),
+        Some(s) if s.is_empty() => String::from("ok"),
+        Some(s) => s
+            .iter_names()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+fn stats_text(stats: &LinkStats) -> String {
+    format!(
+        "requests {}  ok {}  timeouts/io {}  crc {}  device {}  protocol {}  errors {:.1}%",
+        stats.requests,
+        stats.ok,
+        stats.transport_errors,
+        stats.crc_errors,
+        stats.device_errors,
+        stats.protocol_errors,
+        stats.error_rate() * 100.0
+    )
+}
+
+fn draw(frame: &mut Frame, monitor: &mut Monitor) {
+    let [body, footer] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(4)]).areas(frame.area());
+    let [list, detail] =
+        Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(body);
+
+    let header = Row::new([
+        "addr", "firmware", "serial", "P1", "P2", "T", "TOB1", "status",
+    ])
+    .style(Style::new().add_modifier(Modifier::BOLD));
+    let rows = monitor.devices.iter().map(|d| {
+        let mut cells = vec![
+            Cell::from(d.address().to_string()),
+            Cell::from(d.device.identity().to_string()),
+            Cell::from(
+                d.serial
+                    .map_or_else(|| String::from("?"), |s| s.to_string()),
+            ),
+        ];
+        cells.extend(d.latest.iter().map(|v| Cell::from(value_text(*v))));
+        let status = Cell::from(status_text(d.status));
+        cells.push(match d.status {
+            Some(s) if !s.is_empty() => status.style(Style::new().fg(Color::Red)),
+            _ => status,
+        });
+        Row::new(cells)
+    });
+    let widths = [
+        Constraint::Length(4),
+        Constraint::Length(11),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Min(6),
+    ];
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::new().borders(Borders::ALL).title("devices"))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, list, &mut monitor.table);
+
+    match monitor.selected() {
+        Some(i) => draw_detail(frame, detail, &monitor.devices[i]),
+        None => frame.render_widget(
+            Paragraph::new("no devices found").block(Block::new().borders(Borders::ALL)),
+            detail,
+        ),
+    }
+
+    let help = "q quit  ↑/↓ select  z/x zero P1/P2  Z/X reset zero P1/P2  c coefficients";
+    let footer_text = vec![
+        Line::from(stats_text(&monitor.stats)),
+        Line::from(monitor.message.as_str()),
+    ];
+    frame.render_widget(
+        Paragraph::new(footer_text).block(Block::new().borders(Borders::TOP).title(help)),
+        footer,
+    );
+
+    if let Some(rows) = &monitor.coefficients {
+        let area = centered(detail, rows.len() as u16 + 2);
+        let rows = rows
+            .iter()
+            .map(|(name, value)| Row::new([name.as_str(), value.as_str()]));
+        let table = Table::new(rows, [Constraint::Min(24), Constraint::Length(14)]).block(
+            Block::new()
+                .borders(Borders::ALL)
+                .title("coefficients (c to close)"),
+        );
+        frame.render_widget(Clear, area);
+        frame.render_widget(table, area);
+    }
+}
+
+fn draw_detail(frame: &mut Frame, area: Rect, view: &DeviceView) {
+    let [charts, info] = Layout::vertical([Constraint::Min(0), Constraint::Length(3)]).areas(area);
+    let areas = Layout::vertical([Constraint::Ratio(1, CHANNELS.len() as u32); CHANNELS.len()])
+        .split(charts);
+    for ((channel, history), area) in CHANNELS.iter().zip(&view.history).zip(areas.iter()) {
+        let (data, min, max) = sparkline_data(history);
+        let title = if history.is_empty() {
+            format!("{channel:?} -")
+        } else {
+            format!(
+                "{channel:?} {}  [{min:.4} .. {max:.4}]",
+                value_text(history.back().copied())
+            )
+        };
+        let sparkline = Sparkline::default()
+            .block(Block::new().borders(Borders::ALL).title(title))
+            .data(&data)
+            .max(100)
+            .style(Style::new().fg(Color::Cyan));
+        frame.render_widget(sparkline, *area);
+    }
+    let text = format!(
+        "address {}  status {}\n{}",
+        view.address(),
+        status_text(view.status),
+        stats_text(&view.stats)
+    );
+    frame.render_widget(Paragraph::new(text).block(Block::new()), info);
+}
+
+fn centered(area: Rect, height: u16) -> Rect {
+    let height = height.min(area.height);
+    let width = 40.min(area.width);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Scans `from..=to`, then polls every device found until the user quits.
+pub async fn run(bus: Bus, from: u8, to: u8, interval: Duration) -> CliResult {
+    let mut stats = LinkStats::default();
+    eprintln!("scanning addresses {from}..={to}");
+    let (bus, devices) = discover(bus, from, to, &mut stats).await;
+    let Some(first) = devices.first() else {
+        return Err(format!("no devices found between {from} and {to}").into());
+    };
+    let mut dev = bus.resume(first.device);
+    let mut monitor = Monitor {
+        table: TableState::default().with_selected((!devices.is_empty()).then_some(0)),
+        message: format!("{} device(s) found", devices.len()),
+        devices,
+        coefficients: None,
+        stats,
+        pending: None,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut dev, &mut monitor, interval).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    dev: &mut Device,
+    monitor: &mut Monitor,
+    interval: Duration,
+) -> CliResult {
+    let mut next_poll = Instant::now();
+    loop {
+        if Instant::now() >= next_poll {
+            next_poll = Instant::now() + interval;
+            poll_all(dev, monitor).await;
+        }
+        terminal.draw(|frame| draw(frame, monitor))?;
+
+        let wait = next_poll.saturating_duration_since(Instant::now());
+        if !event::poll(wait)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if let Some((i, command)) = monitor.pending.take() {
+            if key.code == KeyCode::Char('y') {
+                zero(dev, monitor, i, command).await;
+            } else {
+                monitor.message = format!("{command:?} cancelled");
+            }
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Up => monitor.table.select_previous(),
+            KeyCode::Down => monitor.table.select_next(),
+            KeyCode::Char('z') => confirm_zero(monitor, ZeroCommands::SetZeroP1),
+            KeyCode::Char('x') => confirm_zero(monitor, ZeroCommands::SetZeroP2),
+            KeyCode::Char('Z') => confirm_zero(monitor, ZeroCommands::ResetZeroP1),
+            KeyCode::Char('X') => confirm_zero(monitor, ZeroCommands::ResetZeroP2),
+            KeyCode::Char('c') => match monitor.coefficients {
+                Some(_) => monitor.coefficients = None,
+                None => inspect(dev, monitor).await,
+            },
+            _ => {}
+        }
+    }
+}
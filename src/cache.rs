@@ -0,0 +1,312 @@
+use crate::base::{
+    Channels, Coefficients, ConfigurationCommands, Identity, KellerErrors, ProtocolError,
+    XLineFrameError, XLineIO, ZeroCommands,
+};
+use crate::{KellerXLine, XLineResult};
+
+/// [`KellerXLine`] that remembers coefficients and configuration entries
+/// once read, so repeated reads stay off the bus.
+///
+/// Each entry is cached on its own. A write through the cache invalidates
+/// the entry it wrote and a zero command invalidates every coefficient;
+/// everything is dropped when F48 reports the device restarted, when the
+/// client re-initialises it automatically, or when a request fails with
+/// [`KellerErrors::DeviceNotInitialized`]. Channel values are never cached.
+pub struct CachedXLine<T: XLineIO> {
+    xline: KellerXLine<T>,
+    coefficients: [Option<f32>; 256],
+    configuration: [Option<u8>; 256],
+    restarts: u32,
+}
+
+impl<T: XLineIO> CachedXLine<T> {
+    pub fn new(xline: KellerXLine<T>) -> Self {
+        Self {
+            restarts: xline.restarts(),
+            xline,
+            coefficients: [None; 256],
+            configuration: [None; 256],
+        }
+    }
+
+    pub fn inner(&self) -> &KellerXLine<T> {
+        &self.xline
+    }
+
+    /// Access to the uncached client. The cache cannot see what is done
+    /// through it, so it is emptied.
+    pub fn inner_mut(&mut self) -> &mut KellerXLine<T> {
+        self.invalidate_all();
+        &mut self.xline
+    }
+
+    pub fn into_inner(self) -> KellerXLine<T> {
+        self.xline
+    }
+
+    pub fn cached_coefficient(&self, coefficient: Coefficients) -> Option<f32> {
+        self.coefficients[coefficient as usize]
+    }
+
+    pub fn cached_configuration(&self, entry: ConfigurationCommands) -> Option<u8> {
+        self.configuration[entry as usize]
+    }
+
+    pub fn invalidate_coefficient(&mut self, coefficient: Coefficients) {
+        self.coefficients[coefficient as usize] = None;
+    }
+
+    pub fn invalidate_configuration(&mut self, entry: ConfigurationCommands) {
+        self.configuration[entry as usize] = None;
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.coefficients = [None; 256];
+        self.configuration = [None; 256];
+    }
+
+    /// Re-reads every entry that is currently cached. On error the entries
+    /// not yet re-read are left invalid.
+    pub async fn refresh(&mut self) -> XLineResult<(), T::Error> {
+        let coefficients = self.coefficients;
+        let configuration = self.configuration;
+        self.invalidate_all();
+        for id in (0..=u8::MAX).filter(|&id| coefficients[id as usize].is_some()) {
+            if let Ok(coefficient) = Coefficients::try_from(id) {
+                self.read_coefficent(coefficient).await?;
+            }
+        }
+        for id in (0..=u8::MAX).filter(|&id| configuration[id as usize].is_some()) {
+            if let Ok(entry) = ConfigurationCommands::try_from(id) {
+                self.read_configuration(entry).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the cache if the device lost its state during the request.
+    fn check<R>(&mut self, result: XLineResult<R, T::Error>) -> XLineResult<R, T::Error> {
+        if self.xline.restarts() != self.restarts {
+            self.restarts = self.xline.restarts();
+            self.invalidate_all();
+        }
+        if let Err(ProtocolError::FrameError(XLineFrameError::DeviceError(
+            KellerErrors::DeviceNotInitialized,
+        ))) = result
+        {
+            self.invalidate_all();
+        }
+        result
+    }
+
+    /// Initialises the device, dropping the cache if it was re-powered.
+    pub async fn init_and_release(&mut self) -> XLineResult<Identity, T::Error> {
+        let identity = self.xline.init_and_release().await?;
+        if identity.was_restarted() {
+            self.invalidate_all();
+        }
+        Ok(identity)
+    }
+
+    pub async fn read_coefficent(
+        &mut self,
+        coefficient: Coefficients,
+    ) -> XLineResult<f32, T::Error> {
+        if let Some(value) = self.coefficients[coefficient as usize] {
+            return Ok(value);
+        }
+        let result = self.xline.read_coefficent(coefficient).await;
+        let value = self.check(result)?;
+        self.coefficients[coefficient as usize] = Some(value);
+        Ok(value)
+    }
+
+    pub async fn write_coefficent(
+        &mut self,
+        coefficient: Coefficients,
+        value: f32,
+    ) -> XLineResult<(), T::Error> {
+        self.invalidate_coefficient(coefficient);
+        let result = self.xline.write_coefficent(coefficient, value).await;
+        self.check(result)
+    }
+
+    pub async fn force_write_coefficent(
+        &mut self,
+        coefficient: Coefficients,
+        value: f32,
+    ) -> XLineResult<(), T::Error> {
+        self.invalidate_coefficient(coefficient);
+        let result = self.xline.force_write_coefficent(coefficient, value).await;
+        self.check(result)
+    }
+
+    pub async fn read_configuration(
+        &mut self,
+        entry: ConfigurationCommands,
+    ) -> XLineResult<u8, T::Error> {
+        if let Some(value) = self.configuration[entry as usize] {
+            return Ok(value);
+        }
+        let result = self.xline.read_configuration(entry).await;
+        let value = self.check(result)?;
+        self.configuration[entry as usize] = Some(value);
+        Ok(value)
+    }
+
+    pub async fn write_configuration(
+        &mut self,
+        entry: ConfigurationCommands,
+        value: u8,
+    ) -> XLineResult<(), T::Error> {
+        self.invalidate_configuration(entry);
+        let result = self.xline.write_configuration(entry, value).await;
+        self.check(result)
+    }
+
+    pub async fn force_write_configuration(
+        &mut self,
+        entry: ConfigurationCommands,
+        value: u8,
+    ) -> XLineResult<(), T::Error> {
+        self.invalidate_configuration(entry);
+        let result = self.xline.force_write_configuration(entry, value).await;
+        self.check(result)
+    }
+
+    pub async fn read_channel_value(&mut self, channel: Channels) -> XLineResult<f32, T::Error> {
+        let result = self.xline.read_channel_value(channel).await;
+        self.check(result)
+    }
+
+    /// Zeroes a channel; the device rewrites offset coefficients, so all of
+    /// them are invalidated.
+    pub async fn zero(&mut self, command: ZeroCommands) -> XLineResult<(), T::Error> {
+        self.coefficients = [None; 256];
+        let result = self.xline.zero(command).await;
+        self.check(result)
+    }
+
+    pub async fn zero_with_value(
+        &mut self,
+        command: ZeroCommands,
+        value: f32,
+    ) -> XLineResult<(), T::Error> {
+        self.coefficients = [None; 256];
+        let result = self.xline.zero_with_value(command, value).await;
+        self.check(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CachedXLine;
+    use crate::base::{
+        Channels, Coefficients as C, ConfigurationCommands as K, KellerErrors, ProtocolError,
+        XLineFrameError, ZeroCommands,
+    };
+    use crate::testing::{Bus, block_on};
+
+    #[test]
+    fn second_read_stays_off_the_bus() {
+        let bus = Bus::new(24);
+        let mut cache = CachedXLine::new(bus.client());
+        assert_eq!(
+            block_on(cache.read_coefficent(C::GainFactorP1)).unwrap(),
+            1.0
+        );
+        assert_eq!(block_on(cache.read_configuration(K::Filter)).unwrap(), 0);
+        assert_eq!(bus.requests.get(), 2);
+
+        let mut device = bus.device.borrow_mut();
+        device.set_coefficient(C::GainFactorP1, Some(2.0));
+        device.set_configuration(K::Filter, Some(3));
+        drop(device);
+        assert_eq!(
+            block_on(cache.read_coefficent(C::GainFactorP1)).unwrap(),
+            1.0
+        );
+        assert_eq!(block_on(cache.read_configuration(K::Filter)).unwrap(), 0);
+        assert_eq!(bus.requests.get(), 2);
+    }
+
+    #[test]
+    fn writes_and_zero_invalidate_entries() {
+        let bus = Bus::new(24);
+        let mut cache = CachedXLine::new(bus.client());
+        block_on(cache.read_coefficent(C::GainFactorP1)).unwrap();
+        block_on(cache.read_coefficent(C::PressureOffsetP1)).unwrap();
+        block_on(cache.read_configuration(K::Filter)).unwrap();
+        block_on(cache.read_configuration(K::SPS)).unwrap();
+
+        block_on(cache.write_coefficent(C::GainFactorP1, 2.0)).unwrap();
+        block_on(cache.write_configuration(K::Filter, 3)).unwrap();
+        assert_eq!(cache.cached_coefficient(C::GainFactorP1), None);
+        assert_eq!(cache.cached_coefficient(C::PressureOffsetP1), Some(0.0));
+        assert_eq!(cache.cached_configuration(K::Filter), None);
+        assert_eq!(cache.cached_configuration(K::SPS), Some(0));
+        assert_eq!(
+            block_on(cache.read_coefficent(C::GainFactorP1)).unwrap(),
+            2.0
+        );
+        assert_eq!(block_on(cache.read_configuration(K::Filter)).unwrap(), 3);
+
+        bus.device.borrow_mut().set_raw(Channels::P1, Some(1.0));
+        block_on(cache.zero(ZeroCommands::SetZeroP1)).unwrap();
+        assert_eq!(cache.cached_coefficient(C::GainFactorP1), None);
+        assert_eq!(cache.cached_coefficient(C::PressureOffsetP1), None);
+        assert_eq!(cache.cached_configuration(K::Filter), Some(3));
+        assert_eq!(
+            block_on(cache.read_coefficent(C::PressureOffsetP1)).unwrap(),
+            -2.0
+        );
+    }
+
+    #[test]
+    fn refresh_rereads_only_cached_entries() {
+        let bus = Bus::new(24);
+        let mut cache = CachedXLine::new(bus.client());
+        block_on(cache.read_coefficent(C::GainFactorP1)).unwrap();
+        block_on(cache.read_configuration(K::Filter)).unwrap();
+
+        let mut device = bus.device.borrow_mut();
+        device.set_coefficient(C::GainFactorP1, Some(2.0));
+        device.set_coefficient(C::PressureOffsetP1, Some(0.5));
+        device.set_configuration(K::Filter, Some(3));
+        drop(device);
+        bus.requests.set(0);
+        block_on(cache.refresh()).unwrap();
+        assert_eq!(bus.requests.get(), 2);
+        assert_eq!(cache.cached_coefficient(C::GainFactorP1), Some(2.0));
+        assert_eq!(cache.cached_configuration(K::Filter), Some(3));
+        assert_eq!(cache.cached_coefficient(C::PressureOffsetP1), None);
+    }
+
+    #[test]
+    fn automatic_reinit_empties_the_cache() {
+        let bus = Bus::new(24);
+        let mut cache = CachedXLine::new(bus.client());
+        block_on(cache.read_coefficent(C::GainFactorP1)).unwrap();
+        bus.device.borrow_mut().power_cycle();
+        block_on(cache.read_channel_value(Channels::P1)).unwrap();
+        assert_eq!(cache.inner().restarts(), 1);
+        assert_eq!(cache.cached_coefficient(C::GainFactorP1), None);
+    }
+
+    #[test]
+    fn device_not_initialized_empties_the_cache() {
+        let bus = Bus::new(24);
+        let mut xline = bus.client();
+        xline.set_auto_reinit(false);
+        let mut cache = CachedXLine::new(xline);
+        block_on(cache.read_configuration(K::Filter)).unwrap();
+        bus.device.borrow_mut().power_cycle();
+        assert!(matches!(
+            block_on(cache.read_channel_value(Channels::P1)),
+            Err(ProtocolError::FrameError(XLineFrameError::DeviceError(
+                KellerErrors::DeviceNotInitialized
+            )))
+        ));
+        assert_eq!(cache.cached_configuration(K::Filter), None);
+    }
+}
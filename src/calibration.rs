@@ -0,0 +1,447 @@
+use crate::base::{Channels, Coefficients, ProtocolError, XLineIO};
+use crate::{KellerXLine, XLineResult};
+
+/// Provides the true pressure at each calibration point, e.g. a pressure
+/// controller or a reference gauge read by an operator.
+#[allow(async_fn_in_trait)]
+pub trait ReferenceSource {
+    type Error;
+
+    /// Sets (or has the operator set) reference point `index` (0 or 1) and
+    /// returns the true value once it is applied and settled.
+    async fn apply(&mut self, index: usize) -> Result<f32, Self::Error>;
+}
+
+/// Errors raised by the calibration helpers.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug)]
+pub enum CalibrationError<TE, RE> {
+    Protocol(ProtocolError<TE>),
+    /// Error from the [`ReferenceSource`].
+    Reference(RE),
+    /// The channel has no offset/gain coefficient pair.
+    UnsupportedChannel(Channels),
+    /// The two readings (or references) coincide, so no gain can be derived.
+    DegeneratePoints,
+    /// The new coefficients did not bring the channel within tolerance and
+    /// the previous ones were written back.
+    OutOfTolerance {
+        max_error: f32,
+    },
+    /// Writing the previous coefficients back failed after `failure`; the
+    /// channel is left in an unknown state.
+    RollbackFailed {
+        failure: CalibrationFailure<TE, RE>,
+        rollback: ProtocolError<TE>,
+    },
+}
+
+impl<TE, RE> From<ProtocolError<TE>> for CalibrationError<TE, RE> {
+    fn from(e: ProtocolError<TE>) -> Self {
+        CalibrationError::Protocol(e)
+    }
+}
+
+/// What went wrong while measuring or confirming a calibration.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug)]
+pub enum CalibrationFailure<TE, RE> {
+    Protocol(ProtocolError<TE>),
+    Reference(RE),
+    OutOfTolerance { max_error: f32 },
+}
+
+impl<TE, RE> From<ProtocolError<TE>> for CalibrationFailure<TE, RE> {
+    fn from(e: ProtocolError<TE>) -> Self {
+        CalibrationFailure::Protocol(e)
+    }
+}
+
+impl<TE, RE> From<CalibrationFailure<TE, RE>> for CalibrationError<TE, RE> {
+    fn from(failure: CalibrationFailure<TE, RE>) -> Self {
+        match failure {
+            CalibrationFailure::Protocol(e) => CalibrationError::Protocol(e),
+            CalibrationFailure::Reference(e) => CalibrationError::Reference(e),
+            CalibrationFailure::OutOfTolerance { max_error } => {
+                CalibrationError::OutOfTolerance { max_error }
+            }
+        }
+    }
+}
+
+/// A reference value and what the device read while it was applied.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CalibrationPoint {
+    pub reference: f32,
+    pub measured: f32,
+}
+
+/// Offset and gain computed from two calibration points, not yet written.
+///
+/// The device reports `raw * gain + offset`; the new pair maps both
+/// measured points onto their references.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TwoPointCalibration {
+    pub channel: Channels,
+    pub points: [CalibrationPoint; 2],
+    pub old_offset: f32,
+    pub old_gain: f32,
+    pub new_offset: f32,
+    pub new_gain: f32,
+}
+
+impl TwoPointCalibration {
+    /// Computes new coefficients from the current ones and two points.
+    pub fn compute(
+        channel: Channels,
+        points: [CalibrationPoint; 2],
+        old_offset: f32,
+        old_gain: f32,
+    ) -> Option<Self> {
+        let [a, b] = points;
+        let raw_a = (a.measured - old_offset) / old_gain;
+        let raw_b = (b.measured - old_offset) / old_gain;
+        if raw_a == raw_b || a.reference == b.reference || !raw_a.is_finite() {
+            return None;
+        }
+        let new_gain = (b.reference - a.reference) / (raw_b - raw_a);
+        let new_offset = a.reference - raw_a * new_gain;
+        if !new_gain.is_finite() || !new_offset.is_finite() {
+            return None;
+        }
+        Some(Self {
+            channel,
+            points,
+            old_offset,
+            old_gain,
+            new_offset,
+            new_gain,
+        })
+    }
+
+    /// What the device will report for a value it reads as `measured` today.
+    pub fn corrected(&self, measured: f32) -> f32 {
+        (measured - self.old_offset) / self.old_gain * self.new_gain + self.new_offset
+    }
+
+    /// Correction at each point: corrected minus measured value.
+    pub fn correction(&self) -> [f32; 2] {
+        self.points.map(|p| self.corrected(p.measured) - p.measured)
+    }
+}
+
+/// Outcome of a calibration that was written and confirmed.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CalibrationReport {
+    pub calibration: TwoPointCalibration,
+    /// Re-measurement at each point with the new coefficients in place.
+    pub verified: [CalibrationPoint; 2],
+    /// Largest absolute difference between reading and reference.
+    pub max_error: f32,
+}
+
+fn coefficients(channel: Channels) -> Option<(Coefficients, Coefficients)> {
+    Some((channel.offset_coefficient()?, channel.gain_coefficient()?))
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    /// Mean of `samples` consecutive readings of `channel` (at least one).
+    pub async fn read_channel_mean(
+        &mut self,
+        channel: Channels,
+        samples: u16,
+    ) -> XLineResult<f32, T::Error> {
+        let samples = samples.max(1);
+        let mut sum = 0.0;
+        for _ in 0..samples {
+            sum += self.read_channel_value(channel).await?;
+        }
+        Ok(sum / samples as f32)
+    }
+
+    async fn measure_points<R: ReferenceSource>(
+        &mut self,
+        channel: Channels,
+        reference: &mut R,
+        samples: u16,
+    ) -> Result<[CalibrationPoint; 2], CalibrationFailure<T::Error, R::Error>> {
+        let mut points = [CalibrationPoint {
+            reference: 0.0,
+            measured: 0.0,
+        }; 2];
+        for (index, point) in points.iter_mut().enumerate() {
+            point.reference = reference
+                .apply(index)
+                .await
+                .map_err(CalibrationFailure::Reference)?;
+            point.measured = self.read_channel_mean(channel, samples).await?;
+        }
+        Ok(points)
+    }
+
+    /// Measures `channel` at both reference points and computes the new
+    /// offset and gain. Nothing is written; inspect the result (for example
+    /// [`TwoPointCalibration::correction`]) before passing it to
+    /// [`Self::apply_calibration`].
+    ///
+    /// Works on any channel with an offset/gain pair: P1 (64/65), P2
+    /// (66/67) and CH0 (70/71).
+    pub async fn measure_two_point<R: ReferenceSource>(
+        &mut self,
+        channel: Channels,
+        reference: &mut R,
+        samples: u16,
+    ) -> Result<TwoPointCalibration, CalibrationError<T::Error, R::Error>> {
+        let (offset, gain) =
+            coefficients(channel).ok_or(CalibrationError::UnsupportedChannel(channel))?;
+        let old_offset = self.read_coefficent(offset).await?;
+        let old_gain = self.read_coefficent(gain).await?;
+        let points = self.measure_points(channel, reference, samples).await?;
+        TwoPointCalibration::compute(channel, points, old_offset, old_gain)
+            .ok_or(CalibrationError::DegeneratePoints)
+    }
+
+    /// Writes the new offset and gain, re-measures both points and restores
+    /// the previous coefficients if any reading is more than `tolerance`
+    /// from its reference.
+    ///
+    /// If a write fails part-way, the previous values are written back
+    /// before the error is returned. The rollback skips validation and the
+    /// write budget, since it only restores what the device held; should it
+    /// fail anyway, [`CalibrationError::RollbackFailed`] carries both
+    /// errors.
+    pub async fn apply_calibration<R: ReferenceSource>(
+        &mut self,
+        calibration: &TwoPointCalibration,
+        reference: &mut R,
+        tolerance: f32,
+        samples: u16,
+    ) -> Result<CalibrationReport, CalibrationError<T::Error, R::Error>> {
+        let channel = calibration.channel;
+        let (offset, gain) =
+            coefficients(channel).ok_or(CalibrationError::UnsupportedChannel(channel))?;
+
+        let failure = match self
+            .confirm_calibration(calibration, (offset, gain), reference, tolerance, samples)
+            .await
+        {
+            Ok(report) => return Ok(report),
+            Err(failure) => failure,
+        };
+        let rollback = match self
+            .write_coefficent_unchecked(offset, calibration.old_offset)
+            .await
+        {
+            Ok(()) => {
+                self.write_coefficent_unchecked(gain, calibration.old_gain)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        Err(match rollback {
+            Ok(()) => failure.into(),
+            Err(rollback) => CalibrationError::RollbackFailed { failure, rollback },
+        })
+    }
+
+    async fn confirm_calibration<R: ReferenceSource>(
+        &mut self,
+        calibration: &TwoPointCalibration,
+        (offset, gain): (Coefficients, Coefficients),
+        reference: &mut R,
+        tolerance: f32,
+        samples: u16,
+    ) -> Result<CalibrationReport, CalibrationFailure<T::Error, R::Error>> {
+        let channel = calibration.channel;
+        self.write_coefficent(offset, calibration.new_offset)
+            .await?;
+        self.write_coefficent(gain, calibration.new_gain).await?;
+
+        let verified = self.measure_points(channel, reference, samples).await?;
+        let max_error = verified
+            .iter()
+            .map(|p| (p.measured - p.reference).abs())
+            .fold(
+                0.0,
+                |max: f32, e| if e.is_nan() || e > max { e } else { max },
+            );
+        if max_error.is_nan() || max_error > tolerance {
+            return Err(CalibrationFailure::OutOfTolerance { max_error });
+        }
+        Ok(CalibrationReport {
+            calibration: *calibration,
+            verified,
+            max_error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::{
+        CalibrationError, CalibrationFailure, CalibrationPoint, ReferenceSource,
+        TwoPointCalibration,
+    };
+    use crate::KellerXLine;
+    use crate::base::{Channels, Coefficients, ProtocolError};
+    use crate::emulator::XLineDevice;
+    use crate::fault::{Fault, FaultInjector};
+    use crate::testing::{TIMEOUT, block_on, client, identity, pressurised};
+
+    /// Applies each point's pressure to the emulated sensor.
+    struct Bench<'a> {
+        p1: &'a Cell<f32>,
+        points: [f32; 2],
+    }
+
+    impl ReferenceSource for Bench<'_> {
+        type Error = ();
+
+        async fn apply(&mut self, index: usize) -> Result<f32, ()> {
+            self.p1.set(self.points[index]);
+            Ok(self.points[index])
+        }
+    }
+
+    /// A reference that never matches what the emulator reads (0.0).
+    struct Unreachable;
+
+    impl ReferenceSource for Unreachable {
+        type Error = ();
+
+        async fn apply(&mut self, _index: usize) -> Result<f32, ()> {
+            Ok(5.0)
+        }
+    }
+
+    fn calibration() -> TwoPointCalibration {
+        let point = CalibrationPoint {
+            reference: 0.0,
+            measured: 0.0,
+        };
+        TwoPointCalibration {
+            channel: Channels::P1,
+            points: [point; 2],
+            old_offset: 0.0,
+            old_gain: 1.0,
+            new_offset: 0.5,
+            new_gain: 1.1,
+        }
+    }
+
+    fn point(reference: f32, measured: f32) -> CalibrationPoint {
+        CalibrationPoint {
+            reference,
+            measured,
+        }
+    }
+
+    #[test]
+    fn compute_maps_both_points_onto_their_references() {
+        // Raw values are (1 - 0.5) / 2 = 0.25 and (6 - 0.5) / 2 = 2.75.
+        let calibration = TwoPointCalibration::compute(
+            Channels::P1,
+            [point(0.0, 1.0), point(10.0, 6.0)],
+            0.5,
+            2.0,
+        )
+        .unwrap();
+        assert_eq!(calibration.new_gain, 4.0);
+        assert_eq!(calibration.new_offset, -1.0);
+        assert_eq!(calibration.corrected(1.0), 0.0);
+        assert_eq!(calibration.corrected(6.0), 10.0);
+        assert_eq!(calibration.correction(), [-1.0, 4.0]);
+    }
+
+    #[test]
+    fn compute_refuses_coinciding_points() {
+        let same_reading = [point(0.0, 1.0), point(10.0, 1.0)];
+        let same_reference = [point(1.0, 1.0), point(1.0, 2.0)];
+        assert_eq!(
+            TwoPointCalibration::compute(Channels::P1, same_reading, 0.0, 1.0),
+            None
+        );
+        assert_eq!(
+            TwoPointCalibration::compute(Channels::P1, same_reference, 0.0, 1.0),
+            None
+        );
+    }
+
+    #[test]
+    fn calibration_corrects_the_reading() {
+        let p1 = Cell::new(0.0);
+        let mut xline = pressurised(24, &p1);
+        let device = &mut xline.transport_mut().device;
+        device.set_coefficient(Coefficients::PressureOffsetP1, Some(1.0));
+        device.set_coefficient(Coefficients::GainFactorP1, Some(2.0));
+        let mut bench = Bench {
+            p1: &p1,
+            points: [0.0, 10.0],
+        };
+
+        let calibration = block_on(xline.measure_two_point(Channels::P1, &mut bench, 4)).unwrap();
+        assert_eq!(calibration.points, [point(0.0, 1.0), point(10.0, 21.0)]);
+        assert_eq!((calibration.new_offset, calibration.new_gain), (0.0, 1.0));
+
+        let report = block_on(xline.apply_calibration(&calibration, &mut bench, 0.001, 4)).unwrap();
+        assert_eq!(report.verified, [point(0.0, 0.0), point(10.0, 10.0)]);
+        assert_eq!(report.max_error, 0.0);
+        p1.set(5.0);
+        assert_eq!(
+            block_on(xline.read_channel_value(Channels::P1)).unwrap(),
+            5.0
+        );
+    }
+
+    #[test]
+    fn rollback_is_not_refused_by_the_write_budget() {
+        let mut xline = client(24);
+        xline.wear_guard_mut().budget = Some(2);
+        let result = block_on(xline.apply_calibration(&calibration(), &mut Unreachable, 0.1, 1));
+        assert!(matches!(
+            result,
+            Err(CalibrationError::OutOfTolerance { .. })
+        ));
+        assert_eq!(
+            block_on(xline.read_coefficent(Coefficients::PressureOffsetP1)).unwrap(),
+            0.0
+        );
+        assert_eq!(
+            block_on(xline.read_coefficent(Coefficients::GainFactorP1)).unwrap(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn failed_rollback_reports_both_errors() {
+        let device = XLineDevice::new(1, identity(24), 4_000_123);
+        let mut injector = FaultInjector::new(device, 1);
+        // F48, two writes, two reads, then the first rollback write.
+        injector.script(&[
+            Fault::None,
+            Fault::None,
+            Fault::None,
+            Fault::None,
+            Fault::None,
+            Fault::WrongAddress,
+        ]);
+        let xline = KellerXLine::new(injector, TIMEOUT, 1).unwrap();
+        let mut xline = block_on(xline.init()).map_err(|e| e.error).unwrap();
+        let result = block_on(xline.apply_calibration(&calibration(), &mut Unreachable, 0.1, 1));
+        assert!(matches!(
+            result,
+            Err(CalibrationError::RollbackFailed {
+                failure: CalibrationFailure::OutOfTolerance { .. },
+                rollback: ProtocolError::WrongAddress,
+            })
+        ));
+    }
+}
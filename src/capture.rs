@@ -0,0 +1,737 @@
+use core::fmt::Debug;
+use core::time::Duration;
+use std::io::{self, Read, Write};
+use std::string::String;
+use std::time::Instant;
+use std::vec::Vec;
+
+use crate::base::XLineIO;
+use crate::emulator::EmulatorError;
+use crate::rs485::Rs485Error;
+
+/// What a captured event was.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    /// Bytes written to the bus.
+    Tx,
+    /// Bytes read from the bus.
+    Rx,
+    /// `clear_rx` call.
+    Clear,
+    /// `flush` call.
+    Flush,
+}
+
+impl Direction {
+    fn to_byte(self) -> u8 {
+        match self {
+            Direction::Tx => 0,
+            Direction::Rx => 1,
+            Direction::Clear => 2,
+            Direction::Flush => 3,
+        }
+    }
+
+    fn from_byte(b: u8) -> Option<Self> {
+        Some(match b {
+            0 => Direction::Tx,
+            1 => Direction::Rx,
+            2 => Direction::Clear,
+            3 => Direction::Flush,
+            _ => return None,
+        })
+    }
+}
+
+/// One transport operation, as seen by [`RecordingTransport`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureEvent {
+    /// Microseconds since the capture started.
+    pub t_us: u64,
+    pub direction: Direction,
+    /// Bytes moved; for a failed read, the bytes that arrived before it
+    /// failed. Empty for clear/flush.
+    #[cfg_attr(feature = "serde", serde(with = "hex_bytes"))]
+    pub data: Vec<u8>,
+    /// Debug rendering of the transport error, if the operation failed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub error: Option<String>,
+    /// Kind of the transport error, if the operation failed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none", with = "error_kind")
+    )]
+    pub error_kind: Option<io::ErrorKind>,
+}
+
+/// Transport errors that can say which [`io::ErrorKind`] they amount to, so
+/// a capture can reproduce them.
+pub trait TransportErrorKind {
+    fn kind(&self) -> io::ErrorKind;
+}
+
+impl TransportErrorKind for io::Error {
+    fn kind(&self) -> io::ErrorKind {
+        io::Error::kind(self)
+    }
+}
+
+impl TransportErrorKind for EmulatorError {
+    fn kind(&self) -> io::ErrorKind {
+        match self {
+            EmulatorError::Timeout => io::ErrorKind::TimedOut,
+        }
+    }
+}
+
+impl<TE: TransportErrorKind, PE> TransportErrorKind for Rs485Error<TE, PE> {
+    fn kind(&self) -> io::ErrorKind {
+        match self {
+            Rs485Error::Transport(e) => e.kind(),
+            Rs485Error::Pin(_) => io::ErrorKind::Other,
+        }
+    }
+}
+
+/// Error kinds a log can carry, by their code in the binary format.
+const ERROR_KINDS: [io::ErrorKind; 20] = [
+    io::ErrorKind::Other,
+    io::ErrorKind::TimedOut,
+    io::ErrorKind::UnexpectedEof,
+    io::ErrorKind::BrokenPipe,
+    io::ErrorKind::ConnectionReset,
+    io::ErrorKind::ConnectionAborted,
+    io::ErrorKind::ConnectionRefused,
+    io::ErrorKind::NotConnected,
+    io::ErrorKind::NotFound,
+    io::ErrorKind::PermissionDenied,
+    io::ErrorKind::AddrInUse,
+    io::ErrorKind::AddrNotAvailable,
+    io::ErrorKind::AlreadyExists,
+    io::ErrorKind::WouldBlock,
+    io::ErrorKind::InvalidInput,
+    io::ErrorKind::InvalidData,
+    io::ErrorKind::WriteZero,
+    io::ErrorKind::Interrupted,
+    io::ErrorKind::Unsupported,
+    io::ErrorKind::OutOfMemory,
+];
+
+/// Code of `kind` in [`ERROR_KINDS`]; kinds not listed are stored as
+/// [`io::ErrorKind::Other`].
+fn error_kind_code(kind: io::ErrorKind) -> u8 {
+    ERROR_KINDS.iter().position(|k| *k == kind).unwrap_or(0) as u8
+}
+
+fn error_kind_from_code(code: u8) -> io::ErrorKind {
+    ERROR_KINDS
+        .get(code as usize)
+        .copied()
+        .unwrap_or(io::ErrorKind::Other)
+}
+
+/// Serialises an [`io::ErrorKind`] by its variant name, e.g. `"TimedOut"`.
+#[cfg(feature = "serde")]
+mod error_kind {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::format;
+    use std::io;
+    use std::string::String;
+
+    use super::ERROR_KINDS;
+
+    pub fn serialize<S: Serializer>(kind: &Option<io::ErrorKind>, s: S) -> Result<S::Ok, S::Error> {
+        match kind {
+            Some(kind) => s.serialize_str(&format!("{kind:?}")),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<io::ErrorKind>, D::Error> {
+        let Some(name) = Option::<String>::deserialize(d)? else {
+            return Ok(None);
+        };
+        Ok(Some(
+            ERROR_KINDS
+                .iter()
+                .copied()
+                .find(|k| format!("{k:?}") == name)
+                .unwrap_or(io::ErrorKind::Other),
+        ))
+    }
+}
+
+/// Destination for captured events.
+pub trait CaptureSink {
+    fn record(&mut self, event: &CaptureEvent) -> io::Result<()>;
+}
+
+impl CaptureSink for Vec<CaptureEvent> {
+    fn record(&mut self, event: &CaptureEvent) -> io::Result<()> {
+        self.push(event.clone());
+        Ok(())
+    }
+}
+
+const BINARY_MAGIC: &[u8; 8] = b"KXLCAP\x02\x00";
+
+/// Compact binary log: an 8-byte header, then per event a little-endian
+/// `u64` timestamp, direction byte, flags byte (bit 0: error), `u16` data
+/// length and data, followed by a `u16`-prefixed error string and an error
+/// kind byte when flagged.
+pub struct BinaryLogWriter<W: Write> {
+    inner: W,
+    header_written: bool,
+}
+
+impl<W: Write> BinaryLogWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            header_written: false,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> CaptureSink for BinaryLogWriter<W> {
+    fn record(&mut self, event: &CaptureEvent) -> io::Result<()> {
+        if !self.header_written {
+            self.inner.write_all(BINARY_MAGIC)?;
+            self.header_written = true;
+        }
+        let data_len = u16::try_from(event.data.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "event too large"))?;
+        self.inner.write_all(&event.t_us.to_le_bytes())?;
+        self.inner
+            .write_all(&[event.direction.to_byte(), event.error.is_some() as u8])?;
+        self.inner.write_all(&data_len.to_le_bytes())?;
+        self.inner.write_all(&event.data)?;
+        if let Some(error) = &event.error {
+            let bytes = truncate(error, u16::MAX as usize).as_bytes();
+            self.inner.write_all(&(bytes.len() as u16).to_le_bytes())?;
+            self.inner.write_all(bytes)?;
+            let kind = event.error_kind.unwrap_or(io::ErrorKind::Other);
+            self.inner.write_all(&[error_kind_code(kind)])?;
+        }
+        self.inner.flush()
+    }
+}
+
+/// The longest prefix of `s` of at most `max` bytes that ends on a char
+/// boundary.
+fn truncate(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Reads a log written by [`BinaryLogWriter`].
+pub fn read_binary_log(mut reader: impl Read) -> io::Result<Vec<CaptureEvent>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+    let mut magic = [0u8; 8];
+    match reader.read_exact(&mut magic) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Vec::new()),
+        res => res?,
+    }
+    if &magic != BINARY_MAGIC {
+        return Err(invalid("not an X-line capture log"));
+    }
+    let mut events = Vec::new();
+    loop {
+        let mut head = [0u8; 12];
+        match reader.read_exact(&mut head) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(events),
+            res => res?,
+        }
+        let t_us = u64::from_le_bytes(head[..8].try_into().unwrap());
+        let direction = Direction::from_byte(head[8]).ok_or_else(|| invalid("bad direction"))?;
+        let mut data = vec![0u8; u16::from_le_bytes([head[10], head[11]]) as usize];
+        reader.read_exact(&mut data)?;
+        let (error, error_kind) = if head[9] & 1 != 0 {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            let mut text = vec![0u8; u16::from_le_bytes(len) as usize];
+            reader.read_exact(&mut text)?;
+            let text = String::from_utf8(text).map_err(|_| invalid("error text is not UTF-8"))?;
+            let mut kind = [0u8];
+            reader.read_exact(&mut kind)?;
+            (Some(text), Some(error_kind_from_code(kind[0])))
+        } else {
+            (None, None)
+        };
+        events.push(CaptureEvent {
+            t_us,
+            direction,
+            data,
+            error,
+            error_kind,
+        });
+    }
+}
+
+/// One JSON object per line, data as a hex string.
+#[cfg(feature = "serde")]
+pub struct JsonlWriter<W: Write> {
+    inner: W,
+}
+
+#[cfg(feature = "serde")]
+impl<W: Write> JsonlWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W: Write> CaptureSink for JsonlWriter<W> {
+    fn record(&mut self, event: &CaptureEvent) -> io::Result<()> {
+        let line = serde_json::to_string(event).map_err(io::Error::other)?;
+        writeln!(self.inner, "{line}")?;
+        self.inner.flush()
+    }
+}
+
+/// Reads a log written by [`JsonlWriter`]; blank lines are skipped.
+#[cfg(feature = "serde")]
+pub fn read_jsonl(reader: impl io::BufRead) -> io::Result<Vec<CaptureEvent>> {
+    let mut events = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        events.push(
+            serde_json::from_str(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        );
+    }
+    Ok(events)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+    use std::string::String;
+    use std::vec::Vec;
+
+    pub fn serialize<S: Serializer>(data: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        let mut out = String::with_capacity(data.len() * 3);
+        for (i, b) in data.iter().enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            out.push_str(&format!("{b:02x}"));
+        }
+        s.serialize_str(&out)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(d)?;
+        text.split_whitespace()
+            .map(|b| u8::from_str_radix(b, 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// Wraps any [`XLineIO`] and records every operation, with its direction and
+/// time since the wrapper was created, into a [`CaptureSink`].
+///
+/// Reads are passed to the inner transport one byte at a time, within the
+/// caller's overall timeout, so a read that fails part way records the bytes
+/// that did arrive.
+///
+/// A failing sink never disturbs the bus traffic; the first sink error is
+/// kept for [`RecordingTransport::take_sink_error`] and recording continues.
+pub struct RecordingTransport<T, S> {
+    inner: T,
+    sink: S,
+    start: Instant,
+    sink_error: Option<io::Error>,
+}
+
+impl<T, S> RecordingTransport<T, S>
+where
+    T: XLineIO,
+    T::Error: Debug + TransportErrorKind,
+    S: CaptureSink,
+{
+    pub fn new(inner: T, sink: S) -> Self {
+        Self {
+            inner,
+            sink,
+            start: Instant::now(),
+            sink_error: None,
+        }
+    }
+
+    pub fn sink(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    pub fn take_sink_error(&mut self) -> Option<io::Error> {
+        self.sink_error.take()
+    }
+
+    pub fn into_inner(self) -> (T, S) {
+        (self.inner, self.sink)
+    }
+
+    fn record<R>(&mut self, direction: Direction, data: &[u8], result: &Result<R, T::Error>) {
+        let event = CaptureEvent {
+            t_us: self.start.elapsed().as_micros() as u64,
+            direction,
+            data: data.to_vec(),
+            error: result.as_ref().err().map(|e| format!("{e:?}")),
+            error_kind: result.as_ref().err().map(|e| e.kind()),
+        };
+        if let Err(e) = self.sink.record(&event) {
+            self.sink_error.get_or_insert(e);
+        }
+    }
+}
+
+impl<T, S> XLineIO for RecordingTransport<T, S>
+where
+    T: XLineIO,
+    T::Error: Debug + TransportErrorKind,
+    S: CaptureSink,
+{
+    type Error = T::Error;
+
+    async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Self::Error> {
+        let result = self.inner.write_all(buf, timeout).await;
+        self.record(Direction::Tx, buf, &result);
+        result
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), Self::Error> {
+        let deadline = Instant::now() + timeout;
+        let mut received = 0;
+        let mut result = Ok(());
+        while received < buf.len() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            result = self
+                .inner
+                .read_exact(&mut buf[received..received + 1], remaining)
+                .await;
+            if result.is_err() {
+                break;
+            }
+            received += 1;
+        }
+        self.record(Direction::Rx, &buf[..received], &result);
+        result
+    }
+
+    async fn clear_rx(&mut self) -> Result<(), Self::Error> {
+        let result = self.inner.clear_rx().await;
+        self.record(Direction::Clear, &[], &result);
+        result
+    }
+
+    async fn flush(&mut self, timeout: Duration) -> Result<(), Self::Error> {
+        let result = self.inner.flush(timeout).await;
+        self.record(Direction::Flush, &[], &result);
+        result
+    }
+}
+
+/// Errors raised by [`ReplayTransport`].
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    #[error("capture exhausted after {0} events")]
+    Exhausted(usize),
+    #[error("event {index}: capture has {expected:?}, client did {actual:?}")]
+    UnexpectedOperation {
+        index: usize,
+        expected: Direction,
+        actual: Direction,
+    },
+    #[error("event {index}: client sent {actual:02x?}, capture has {expected:02x?}")]
+    TxMismatch {
+        index: usize,
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+    #[error("event {index}: client read {requested} bytes, capture has {recorded}")]
+    RxLength {
+        index: usize,
+        recorded: usize,
+        requested: usize,
+    },
+    /// The captured operation failed; the original error's kind and
+    /// rendering.
+    #[error("captured transport error: {message}")]
+    Recorded {
+        kind: io::ErrorKind,
+        message: String,
+    },
+}
+
+impl TransportErrorKind for ReplayError {
+    /// The recorded kind for [`ReplayError::Recorded`]; a replay that
+    /// diverged from the capture is [`io::ErrorKind::Other`].
+    fn kind(&self) -> io::ErrorKind {
+        match self {
+            ReplayError::Recorded { kind, .. } => *kind,
+            _ => io::ErrorKind::Other,
+        }
+    }
+}
+
+impl From<ReplayError> for io::Error {
+    fn from(error: ReplayError) -> Self {
+        io::Error::new(TransportErrorKind::kind(&error), error)
+    }
+}
+
+/// Plays a capture back to a client, checking that it sends exactly what
+/// was recorded and answering with exactly what was received, including
+/// failures: a failed read hands over the bytes that arrived before it
+/// failed and then fails with the recorded [`io::ErrorKind`].
+///
+/// By default events are replayed as fast as the client asks for them;
+/// [`ReplayTransport::set_realtime`] sleeps to reproduce the recorded timing.
+pub struct ReplayTransport {
+    events: Vec<CaptureEvent>,
+    pos: usize,
+    realtime: bool,
+    start: Option<tokio::time::Instant>,
+}
+
+impl ReplayTransport {
+    pub fn new(events: Vec<CaptureEvent>) -> Self {
+        Self {
+            events,
+            pos: 0,
+            realtime: false,
+            start: None,
+        }
+    }
+
+    pub fn from_binary_log(reader: impl Read) -> io::Result<Self> {
+        Ok(Self::new(read_binary_log(reader)?))
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_jsonl(reader: impl io::BufRead) -> io::Result<Self> {
+        Ok(Self::new(read_jsonl(reader)?))
+    }
+
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.realtime = realtime;
+    }
+
+    /// Events not yet replayed.
+    pub fn remaining(&self) -> usize {
+        self.events.len() - self.pos
+    }
+
+    async fn next(&mut self, actual: Direction) -> Result<(usize, &CaptureEvent), ReplayError> {
+        let index = self.pos;
+        let event = self
+            .events
+            .get(index)
+            .ok_or(ReplayError::Exhausted(index))?;
+        if event.direction != actual {
+            return Err(ReplayError::UnexpectedOperation {
+                index,
+                expected: event.direction,
+                actual,
+            });
+        }
+        self.pos += 1;
+        if self.realtime {
+            let start = *self.start.get_or_insert_with(tokio::time::Instant::now);
+            tokio::time::sleep_until(start + Duration::from_micros(event.t_us)).await;
+        }
+        Ok((index, event))
+    }
+
+    fn recorded_error(event: &CaptureEvent) -> Result<(), ReplayError> {
+        match &event.error {
+            Some(message) => Err(ReplayError::Recorded {
+                kind: event.error_kind.unwrap_or(io::ErrorKind::Other),
+                message: message.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl XLineIO for ReplayTransport {
+    type Error = ReplayError;
+
+    async fn write_all(&mut self, buf: &[u8], _timeout: Duration) -> Result<(), Self::Error> {
+        let (index, event) = self.next(Direction::Tx).await?;
+        Self::recorded_error(event)?;
+        if event.data != buf {
+            return Err(ReplayError::TxMismatch {
+                index,
+                expected: event.data.clone(),
+                actual: buf.to_vec(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<(), Self::Error> {
+        let (index, event) = self.next(Direction::Rx).await?;
+        let complete = event.error.is_none();
+        if event.data.len() > buf.len() || (complete && event.data.len() != buf.len()) {
+            return Err(ReplayError::RxLength {
+                index,
+                recorded: event.data.len(),
+                requested: buf.len(),
+            });
+        }
+        buf[..event.data.len()].copy_from_slice(&event.data);
+        Self::recorded_error(event)
+    }
+
+    async fn clear_rx(&mut self) -> Result<(), Self::Error> {
+        let (_, event) = self.next(Direction::Clear).await?;
+        Self::recorded_error(event)
+    }
+
+    async fn flush(&mut self, _timeout: Duration) -> Result<(), Self::Error> {
+        let (_, event) = self.next(Direction::Flush).await?;
+        Self::recorded_error(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Debug;
+    use std::format;
+    use std::io;
+    use std::string::String;
+    use std::vec::Vec;
+
+    use super::{
+        BinaryLogWriter, CaptureEvent, Direction, RecordingTransport, ReplayTransport,
+        TransportErrorKind, read_binary_log,
+    };
+    use crate::base::{ProtocolError, XLineIO};
+    use crate::emulator::XLineDevice;
+    use crate::fault::{Fault, FaultInjector};
+    use crate::testing::{TIMEOUT, block_on, identity};
+    use crate::{KellerXLine, XLineResult};
+
+    fn outcome<R: Debug, E: TransportErrorKind>(result: XLineResult<R, E>) -> String {
+        match result {
+            Ok(value) => format!("{value:?}"),
+            Err(ProtocolError::Transport(e)) => format!("transport {:?}", e.kind()),
+            Err(ProtocolError::FrameError(e)) => format!("{e:?}"),
+            Err(ProtocolError::WrongAddress) => String::from("wrong address"),
+            Err(_) => String::from("other"),
+        }
+    }
+
+    /// Runs the recorded requests over `transport` and lists their outcomes.
+    fn session<T: XLineIO>(transport: T) -> (Vec<String>, T)
+    where
+        T::Error: TransportErrorKind + Debug,
+    {
+        let xline = KellerXLine::new(transport, TIMEOUT, 1).unwrap();
+        let mut xline = block_on(xline.init()).ok().unwrap();
+        let outcomes = (0..5)
+            .map(|_| outcome(block_on(xline.read_serial_number())))
+            .collect();
+        (outcomes, xline.into_transport())
+    }
+
+    fn record() -> (Vec<String>, Vec<CaptureEvent>) {
+        let device = XLineDevice::new(1, identity(24), 4_000_123);
+        let mut injector = FaultInjector::new(device, 3);
+        injector.script(&[
+            Fault::None,
+            Fault::None,
+            Fault::Truncate,
+            Fault::None,
+            Fault::CorruptCrc,
+            Fault::WrongAddress,
+        ]);
+        let (outcomes, recording) = session(RecordingTransport::new(injector, Vec::new()));
+        (outcomes, recording.into_inner().1)
+    }
+
+    #[test]
+    fn failed_reads_keep_their_kind_and_partial_bytes() {
+        let (_, events) = record();
+        let failed = events
+            .iter()
+            .find(|e| e.direction == Direction::Rx && e.error.is_some())
+            .unwrap();
+        assert_eq!(failed.error_kind, Some(io::ErrorKind::TimedOut));
+        assert!(!failed.data.is_empty());
+    }
+
+    #[test]
+    fn replay_reproduces_the_recorded_session() {
+        let (outcomes, events) = record();
+        assert_eq!(outcomes[1], "transport TimedOut");
+        let (replayed, replay) = session(ReplayTransport::new(events));
+        assert_eq!(replayed, outcomes);
+        assert_eq!(replay.remaining(), 0);
+    }
+
+    #[test]
+    fn binary_log_round_trips_error_kinds() {
+        let (_, events) = record();
+        let mut writer = BinaryLogWriter::new(Vec::new());
+        for event in &events {
+            super::CaptureSink::record(&mut writer, event).unwrap();
+        }
+        let read = read_binary_log(writer.into_inner().as_slice()).unwrap();
+        assert_eq!(read, events);
+    }
+
+    #[test]
+    fn long_error_text_is_cut_on_a_char_boundary() {
+        // 'é' is two bytes, so u16::MAX bytes would split the last one.
+        let error = "é".repeat(u16::MAX as usize);
+        let event = CaptureEvent {
+            t_us: 7,
+            direction: Direction::Rx,
+            data: std::vec![],
+            error: Some(error.clone()),
+            error_kind: Some(io::ErrorKind::TimedOut),
+        };
+        let mut writer = BinaryLogWriter::new(Vec::new());
+        super::CaptureSink::record(&mut writer, &event).unwrap();
+        let read = read_binary_log(writer.into_inner().as_slice()).unwrap();
+        let text = read[0].error.as_deref().unwrap();
+        assert_eq!(text.len(), u16::MAX as usize - 1);
+        assert!(error.starts_with(text));
+        assert_eq!(read[0].error_kind, Some(io::ErrorKind::TimedOut));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn jsonl_round_trips_error_kinds() {
+        let (_, events) = record();
+        let mut writer = super::JsonlWriter::new(Vec::new());
+        for event in &events {
+            super::CaptureSink::record(&mut writer, event).unwrap();
+        }
+        let read = super::read_jsonl(writer.into_inner().as_slice()).unwrap();
+        assert_eq!(read, events);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::base::{Channels, Coefficients, ConfigurationCommands, Identity, ZeroCommands};
+
+/// X-line firmware family, from the class and group F48 reports.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FirmwareVariant {
+    /// v5.20: pressure with CH0, analogue output and switching outputs.
+    V5_20,
+    /// v5.21: pressure with conductivity.
+    V5_21,
+    /// v5.24: pressure with CH0, analogue output and the CH0 curve fit.
+    V5_24,
+}
+
+/// Something a request refers to that the device's firmware lacks.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnsupportedEntry {
+    Coefficient(Coefficients),
+    Channel(Channels),
+    Configuration(ConfigurationCommands),
+    Zero(ZeroCommands),
+}
+
+impl core::fmt::Display for UnsupportedEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            UnsupportedEntry::Coefficient(c) => write!(f, "coefficient {} ({c:?})", *c as u8),
+            UnsupportedEntry::Channel(c) => write!(f, "channel {c:?}"),
+            UnsupportedEntry::Configuration(c) => {
+                write!(f, "configuration entry {} ({c:?})", *c as u8)
+            }
+            UnsupportedEntry::Zero(c) => write!(f, "zero command {c:?}"),
+        }
+    }
+}
+
+impl FirmwareVariant {
+    pub fn from_identity(identity: &Identity) -> Option<Self> {
+        match (identity.class, identity.group) {
+            (5, 20) => Some(FirmwareVariant::V5_20),
+            (5, 21) => Some(FirmwareVariant::V5_21),
+            (5, 24) => Some(FirmwareVariant::V5_24),
+            _ => None,
+        }
+    }
+
+    fn has_ch0(self) -> bool {
+        matches!(self, FirmwareVariant::V5_20 | FirmwareVariant::V5_24)
+    }
+
+    /// Whether the firmware implements `coefficient`. ID 72 exists on every
+    /// variant but is the T offset only on v5.21/v5.24; on v5.20 it is the
+    /// upper threshold of switching output 1.
+    pub fn supports_coefficient(self, coefficient: Coefficients) -> bool {
+        use Coefficients::*;
+        match coefficient {
+            ThresholdSquareRoot
+            | PressureOffsetP1
+            | GainFactorP1
+            | PressureOffsetP2
+            | GainFactorP2
+            | TemperatureOffsetTorUpperThresSw1 => true,
+            Free100 | Free101 | Free102 | Free103 | Free104 | Free105 | Free106 | Free107
+            | Free108 | Free109 | Free110 | Free111 => true,
+            OffsetAnalogOutput | GainFactorAnalogOutput | OffsetCh0 | GainFactorCh0 => {
+                self.has_ch0()
+            }
+            LowerThresSw1 | UpperThresSw2 | LowerThresSw2 => self == FirmwareVariant::V5_20,
+            TemperatureOffsetTOB1 | TemperatureOffsetTOB2 => self != FirmwareVariant::V5_20,
+            GainCondRange1
+            | GainCondRange2
+            | GainCondRange3
+            | GainCondRange4
+            | ConductivityTempCoeff
+            | ConductivityCellConstant => self == FirmwareVariant::V5_21,
+            Ch0CurveP1_140 | Ch0CurveP1_141 | Ch0CurveP1_142 | Ch0CurveP1_143 | Ch0CurveP1_144
+            | Ch0CurveP1_145 | Ch0CurveP1_146 | Ch0CurveP1_147 | Ch0CurveP1_148
+            | Ch0CurveP1_149 | Ch0CurveP1_150 | Ch0CurveP1_151 | Ch0CurveP1_152
+            | Ch0CurveP1_153 | Ch0CurveP1_154 | Ch0CurveP1_155 | Ch0CurveP1_156 => {
+                self == FirmwareVariant::V5_24
+            }
+        }
+    }
+
+    pub fn supports_channel(self, channel: Channels) -> bool {
+        match channel {
+            Channels::P1 | Channels::P2 | Channels::T | Channels::TOB1 | Channels::TOB2 => true,
+            Channels::CH0 => self.has_ch0(),
+            Channels::ConTc | Channels::ConRaw => self == FirmwareVariant::V5_21,
+        }
+    }
+
+    pub fn supports_configuration(self, entry: ConfigurationCommands) -> bool {
+        use ConfigurationCommands::*;
+        match entry {
+            ConOn | ConRange | ConTempCompMode => self == FirmwareVariant::V5_21,
+            _ => true,
+        }
+    }
+
+    /// Whether F95 can zero the command's channel: the channel must exist
+    /// and have an offset coefficient, which T lacks on v5.20.
+    pub fn supports_zero(self, command: ZeroCommands) -> bool {
+        let channel = command.channel();
+        self.supports_channel(channel) && self.offset_coefficient(channel).is_some()
+    }
+
+    /// Offset coefficient the firmware applies to `channel`, taking the
+    /// v5.20 meaning of ID 72 into account.
+    pub fn offset_coefficient(self, channel: Channels) -> Option<Coefficients> {
+        if channel == Channels::T && self == FirmwareVariant::V5_20 {
+            return None;
+        }
+        channel
+            .offset_coefficient()
+            .filter(|c| self.supports_coefficient(*c))
+    }
+
+    /// Gain coefficient the firmware applies to `channel`.
+    pub fn gain_coefficient(self, channel: Channels) -> Option<Coefficients> {
+        channel
+            .gain_coefficient()
+            .filter(|c| self.supports_coefficient(*c))
+    }
+
+    /// Every coefficient the firmware implements, in ID order.
+    pub fn coefficients(self) -> impl Iterator<Item = Coefficients> {
+        (0..=u8::MAX)
+            .filter_map(|id| Coefficients::try_from(id).ok())
+            .filter(move |c| self.supports_coefficient(*c))
+    }
+
+    /// Every configuration entry the firmware implements, in ID order.
+    pub fn configuration(self) -> impl Iterator<Item = ConfigurationCommands> {
+        (0..=u8::MAX)
+            .filter_map(|id| ConfigurationCommands::try_from(id).ok())
+            .filter(move |c| self.supports_configuration(*c))
+    }
+
+    /// Every channel the firmware implements, in ID order.
+    pub fn channels(self) -> impl Iterator<Item = Channels> {
+        (0..=u8::MAX)
+            .filter_map(|id| Channels::try_from(id).ok())
+            .filter(move |c| self.supports_channel(*c))
+    }
+}
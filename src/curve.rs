@@ -0,0 +1,356 @@
+//! CH0 curve upload for coefficients 140…156.
+//!
+//! Unstable: the coefficient layout below (a breakpoint count followed by
+//! `CH0, P1` pairs) has not been checked against a Keller specification;
+//! the register list only says the entries hold "coefficients for CH0
+//! straight-line curve fitting of P1". The module is compiled only with the
+//! `unstable-curve` feature until the layout is confirmed on a device.
+
+use crate::base::{Coefficients, ProtocolError, XLineIO};
+use crate::{KellerXLine, XLineResult};
+
+/// Breakpoints the v5.24 firmware holds in coefficients 140…156.
+pub const CURVE_BREAKPOINTS: usize = 8;
+/// Coefficients used by the encoded curve (140…156).
+pub const CURVE_COEFFICIENTS: usize = 1 + 2 * CURVE_BREAKPOINTS;
+/// Largest number of calibration points [`Ch0Curve::from_points`] accepts.
+pub const MAX_CURVE_POINTS: usize = 64;
+
+const FIRST_COEFFICIENT: u8 = Coefficients::Ch0CurveP1_140 as u8;
+
+/// A P1 reference and the CH0 reading taken while it was applied.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CurvePoint {
+    pub p1: f32,
+    pub ch0: f32,
+}
+
+/// Errors raised while building or decoding a curve.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CurveError {
+    /// Fewer than two distinct CH0 values.
+    TooFewPoints,
+    /// More than [`MAX_CURVE_POINTS`] points.
+    TooManyPoints,
+    /// A point contains NaN or an infinity.
+    NonFinite,
+    /// Coefficients 140…156 do not hold a valid curve.
+    InvalidEncoding,
+}
+
+/// Errors raised by [`KellerXLine::upload_ch0_curve`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug)]
+pub enum CurveUploadError<TE> {
+    Protocol(ProtocolError<TE>),
+    Curve(CurveError),
+    /// The coefficients read back differ from those written.
+    ReadBackMismatch,
+}
+
+impl<TE> From<ProtocolError<TE>> for CurveUploadError<TE> {
+    fn from(e: ProtocolError<TE>) -> Self {
+        CurveUploadError::Protocol(e)
+    }
+}
+
+impl<TE> From<CurveError> for CurveUploadError<TE> {
+    fn from(e: CurveError) -> Self {
+        CurveUploadError::Curve(e)
+    }
+}
+
+/// Piecewise straight-line mapping from CH0 to P1, as evaluated by the
+/// v5.24 firmware: linear interpolation between up to eight breakpoints
+/// sorted by CH0, extended along the first and last segment outside them.
+///
+/// In coefficients 140…156 it is stored as the breakpoint count (140)
+/// followed by `CH0, P1` pairs (141/142, 143/144, …); unused pairs are zero.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ch0Curve {
+    len: u8,
+    breakpoints: [CurvePoint; CURVE_BREAKPOINTS],
+}
+
+impl Ch0Curve {
+    /// Builds a curve that interpolates `points`.
+    ///
+    /// Points sharing a CH0 value are averaged. With more than eight
+    /// distinct values, the breakpoint whose removal adds the least error is
+    /// dropped, one at a time, until eight remain; the first and last are
+    /// always kept. This greedy simplification is not a least-squares fit:
+    /// the kept breakpoints pass exactly through their points.
+    pub fn from_points(points: &[CurvePoint]) -> Result<Self, CurveError> {
+        if points.len() > MAX_CURVE_POINTS {
+            return Err(CurveError::TooManyPoints);
+        }
+        if points
+            .iter()
+            .any(|p| !p.p1.is_finite() || !p.ch0.is_finite())
+        {
+            return Err(CurveError::NonFinite);
+        }
+
+        let mut sorted = [CurvePoint::default(); MAX_CURVE_POINTS];
+        sorted[..points.len()].copy_from_slice(points);
+        let sorted = &mut sorted[..points.len()];
+        sorted.sort_unstable_by(|a, b| a.ch0.total_cmp(&b.ch0));
+
+        // Merge equal CH0 readings into one averaged point.
+        let mut unique = [CurvePoint::default(); MAX_CURVE_POINTS];
+        let mut len = 0;
+        let mut i = 0;
+        while i < sorted.len() {
+            let mut j = i;
+            let mut sum = 0.0;
+            while j < sorted.len() && sorted[j].ch0 == sorted[i].ch0 {
+                sum += sorted[j].p1;
+                j += 1;
+            }
+            unique[len] = CurvePoint {
+                p1: sum / (j - i) as f32,
+                ch0: sorted[i].ch0,
+            };
+            len += 1;
+            i = j;
+        }
+        let unique = &unique[..len];
+        if unique.len() < 2 {
+            return Err(CurveError::TooFewPoints);
+        }
+
+        let mut kept = [true; MAX_CURVE_POINTS];
+        let mut kept_count = unique.len();
+        while kept_count > CURVE_BREAKPOINTS {
+            let mut best = (f32::INFINITY, 0);
+            for k in 1..unique.len() - 1 {
+                if !kept[k] {
+                    continue;
+                }
+                let prev = (0..k).rev().find(|&i| kept[i]).unwrap_or(0);
+                let next = (k + 1..unique.len()).find(|&i| kept[i]).unwrap_or(k);
+                let error = (prev + 1..next)
+                    .map(|j| {
+                        (interpolate(unique[prev], unique[next], unique[j].ch0) - unique[j].p1)
+                            .abs()
+                    })
+                    .fold(0.0, f32::max);
+                if error < best.0 {
+                    best = (error, k);
+                }
+            }
+            kept[best.1] = false;
+            kept_count -= 1;
+        }
+
+        let mut curve = Ch0Curve {
+            len: 0,
+            breakpoints: [CurvePoint::default(); CURVE_BREAKPOINTS],
+        };
+        for (point, _) in unique.iter().zip(kept).filter(|(_, keep)| *keep) {
+            curve.breakpoints[curve.len as usize] = *point;
+            curve.len += 1;
+        }
+        Ok(curve)
+    }
+
+    pub fn breakpoints(&self) -> &[CurvePoint] {
+        &self.breakpoints[..self.len as usize]
+    }
+
+    /// P1 the curve assigns to a CH0 reading.
+    pub fn evaluate(&self, ch0: f32) -> f32 {
+        let points = self.breakpoints();
+        let segment = points[1..points.len() - 1]
+            .iter()
+            .position(|p| ch0 < p.ch0)
+            .unwrap_or(points.len() - 2);
+        interpolate(points[segment], points[segment + 1], ch0)
+    }
+
+    /// Curve value minus reference P1 at `point`.
+    pub fn residual(&self, point: &CurvePoint) -> f32 {
+        self.evaluate(point.ch0) - point.p1
+    }
+
+    /// Values for coefficients 140…156, in order.
+    pub fn encode(&self) -> [f32; CURVE_COEFFICIENTS] {
+        let mut out = [0.0; CURVE_COEFFICIENTS];
+        out[0] = self.len as f32;
+        for (pair, point) in out[1..].chunks_exact_mut(2).zip(self.breakpoints()) {
+            pair[0] = point.ch0;
+            pair[1] = point.p1;
+        }
+        out
+    }
+
+    /// Parses the values of coefficients 140…156.
+    pub fn decode(values: &[f32; CURVE_COEFFICIENTS]) -> Result<Self, CurveError> {
+        let len = values[0];
+        if !(2.0..=CURVE_BREAKPOINTS as f32).contains(&len) || len != (len as u8) as f32 {
+            return Err(CurveError::InvalidEncoding);
+        }
+        let mut curve = Ch0Curve {
+            len: len as u8,
+            breakpoints: [CurvePoint::default(); CURVE_BREAKPOINTS],
+        };
+        for (point, pair) in curve.breakpoints[..len as usize]
+            .iter_mut()
+            .zip(values[1..].chunks_exact(2))
+        {
+            *point = CurvePoint {
+                ch0: pair[0],
+                p1: pair[1],
+            };
+        }
+        let ordered = curve
+            .breakpoints()
+            .windows(2)
+            .all(|w| w[0].ch0 < w[1].ch0 && w[1].p1.is_finite());
+        if !ordered || !curve.breakpoints[0].p1.is_finite() {
+            return Err(CurveError::InvalidEncoding);
+        }
+        Ok(curve)
+    }
+}
+
+fn interpolate(a: CurvePoint, b: CurvePoint, ch0: f32) -> f32 {
+    a.p1 + (ch0 - a.ch0) * (b.p1 - a.p1) / (b.ch0 - a.ch0)
+}
+
+/// Curve as read back from the device, with the residual at every
+/// calibration point in the order they were given.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CurveUploadReport {
+    pub curve: Ch0Curve,
+    len: usize,
+    residuals: [f32; MAX_CURVE_POINTS],
+}
+
+impl CurveUploadReport {
+    pub fn residuals(&self) -> &[f32] {
+        &self.residuals[..self.len]
+    }
+
+    /// Largest absolute residual.
+    pub fn max_residual(&self) -> f32 {
+        self.residuals().iter().fold(0.0, |max, r| r.abs().max(max))
+    }
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    /// Reads coefficients 140…156 and decodes them.
+    pub async fn read_ch0_curve(&mut self) -> Result<Ch0Curve, CurveUploadError<T::Error>> {
+        let values = self.read_curve_coefficients().await?;
+        Ok(Ch0Curve::decode(&values)?)
+    }
+
+    /// Writes `curve` to coefficients 140…156.
+    pub async fn write_ch0_curve(&mut self, curve: &Ch0Curve) -> XLineResult<(), T::Error> {
+        for (offset, value) in curve.encode().into_iter().enumerate() {
+            self.write_coefficent(curve_coefficient(offset), value)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Builds a curve from `points` with [`Ch0Curve::from_points`],
+    /// uploads it, reads it back and reports the residual at every point.
+    /// v5.24 firmware only.
+    pub async fn upload_ch0_curve(
+        &mut self,
+        points: &[CurvePoint],
+    ) -> Result<CurveUploadReport, CurveUploadError<T::Error>> {
+        let curve = Ch0Curve::from_points(points)?;
+        self.write_ch0_curve(&curve).await?;
+        let values = self.read_curve_coefficients().await?;
+        let written = curve.encode();
+        if values
+            .iter()
+            .zip(&written)
+            .any(|(a, b)| a.to_bits() != b.to_bits())
+        {
+            return Err(CurveUploadError::ReadBackMismatch);
+        }
+        let curve = Ch0Curve::decode(&values)?;
+        let mut report = CurveUploadReport {
+            curve,
+            len: points.len(),
+            residuals: [0.0; MAX_CURVE_POINTS],
+        };
+        for (residual, point) in report.residuals.iter_mut().zip(points) {
+            *residual = curve.residual(point);
+        }
+        Ok(report)
+    }
+
+    async fn read_curve_coefficients(
+        &mut self,
+    ) -> XLineResult<[f32; CURVE_COEFFICIENTS], T::Error> {
+        let mut values = [0.0; CURVE_COEFFICIENTS];
+        for (offset, value) in values.iter_mut().enumerate() {
+            *value = self.read_coefficent(curve_coefficient(offset)).await?;
+        }
+        Ok(values)
+    }
+}
+
+fn curve_coefficient(offset: usize) -> Coefficients {
+    // 140…156 are all defined, so this cannot fail.
+    Coefficients::try_from(FIRST_COEFFICIENT + offset as u8).unwrap_or(Coefficients::Ch0CurveP1_140)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ch0Curve, CurveError, CurvePoint};
+    use crate::testing::{block_on, client};
+
+    fn point(ch0: f32, p1: f32) -> CurvePoint {
+        CurvePoint { p1, ch0 }
+    }
+
+    #[test]
+    fn few_points_are_kept_as_breakpoints() {
+        let points = [point(2.0, 20.0), point(0.0, 0.0), point(1.0, 12.0)];
+        let curve = Ch0Curve::from_points(&points).unwrap();
+        assert_eq!(curve.breakpoints().len(), 3);
+        assert!(points.iter().all(|p| curve.residual(p) == 0.0));
+        assert_eq!(curve.evaluate(3.0), 28.0);
+    }
+
+    #[test]
+    fn collinear_points_are_dropped_first() {
+        let mut points = [CurvePoint::default(); 12];
+        for (i, p) in points.iter_mut().enumerate() {
+            let ch0 = i as f32;
+            // A kink at 6; everything else lies on two straight lines.
+            *p = point(ch0, if i < 6 { ch0 } else { 6.0 + 3.0 * (ch0 - 6.0) });
+        }
+        let curve = Ch0Curve::from_points(&points).unwrap();
+        assert_eq!(curve.breakpoints().len(), 8);
+        assert!(points.iter().all(|p| curve.residual(p).abs() < 1e-5));
+    }
+
+    #[test]
+    fn a_single_ch0_value_is_too_few_points() {
+        assert_eq!(
+            Ch0Curve::from_points(&[point(1.0, 0.0), point(1.0, 2.0)]),
+            Err(CurveError::TooFewPoints)
+        );
+    }
+
+    #[test]
+    fn upload_reads_the_curve_back() {
+        let mut xline = client(24);
+        let points = [point(0.0, 0.0), point(1.0, 10.0), point(2.0, 30.0)];
+        let report = block_on(xline.upload_ch0_curve(&points)).unwrap();
+        assert_eq!(report.max_residual(), 0.0);
+        assert_eq!(block_on(xline.read_ch0_curve()).unwrap(), report.curve);
+    }
+}
@@ -0,0 +1,635 @@
+use core::time::Duration;
+
+use crate::TRANSPARENT_ADDRESS;
+use crate::base::{
+    Channels, Coefficients, ConfigurationCommands, FunctionCodes, Identity, KellerErrors,
+    XLineFrame, XLineFrameError, XLineIO, ZeroCommands, crc16_hi_lo, f32_from_be_bytes,
+};
+use crate::catalog::FirmwareVariant;
+use crate::registry::{COEFFICIENTS, CONFIGURATION};
+
+/// Largest reply the emulator produces (F48: 10 bytes).
+const MAX_REPLY: usize = 16;
+const TX_CAP: usize = 64;
+const CHANNEL_SLOTS: usize = 12;
+
+/// Errors raised by the emulator's loopback [`XLineIO`] implementation.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EmulatorError {
+    /// Fewer bytes were queued than the caller asked for.
+    Timeout,
+}
+
+/// In-memory X-line device: the server side of the protocol.
+///
+/// [`XLineDevice::process`] turns one request frame into its reply, so the
+/// emulator can sit behind any byte transport. It also implements
+/// [`XLineIO`] directly as a loopback, which lets a [`crate::KellerXLine`]
+/// talk to it without any I/O.
+///
+/// Like real hardware the emulator starts out uninitialised and answers
+/// everything but F48 with [`KellerErrors::DeviceNotInitialized`]. Channel
+/// readings are the raw value scaled by the channel's gain coefficient plus
+/// its offset coefficient, so zeroing and calibration behave as on a device.
+pub struct XLineDevice {
+    address: u8,
+    identity: Identity,
+    serial_number: u32,
+    initialized: bool,
+    restarted: bool,
+    fail_saves: bool,
+    status: u8,
+    eeprom_writes: u32,
+    coefficients: [Option<f32>; 256],
+    configuration: [Option<u8>; 256],
+    raw: [Option<f32>; CHANNEL_SLOTS],
+    tx: [u8; TX_CAP],
+    tx_len: usize,
+}
+
+impl XLineDevice {
+    /// Creates a freshly powered-up device whose coefficients, configuration
+    /// and channels follow the firmware variant in `identity.group`
+    /// (20, 21 or 24).
+    pub fn new(address: u8, identity: Identity, serial_number: u32) -> Self {
+        let mut device = Self {
+            address,
+            identity,
+            serial_number,
+            initialized: false,
+            restarted: true,
+            fail_saves: false,
+            status: 0,
+            eeprom_writes: 0,
+            coefficients: [None; 256],
+            configuration: [None; 256],
+            raw: [None; CHANNEL_SLOTS],
+            tx: [0; TX_CAP],
+            tx_len: 0,
+        };
+        device.load_defaults();
+        device
+    }
+
+    /// Fills in every entry the firmware variant implements with its
+    /// registry default. An unknown group gets the entries all variants
+    /// share.
+    fn load_defaults(&mut self) {
+        let variant = FirmwareVariant::from_identity(&self.identity);
+        let all = [
+            FirmwareVariant::V5_20,
+            FirmwareVariant::V5_21,
+            FirmwareVariant::V5_24,
+        ];
+        let supported = |f: &dyn Fn(FirmwareVariant) -> bool| match variant {
+            Some(v) => f(v),
+            None => all.iter().all(|v| f(*v)),
+        };
+        for info in &COEFFICIENTS {
+            if supported(&|v| v.supports_coefficient(info.id)) {
+                self.coefficients[info.id as usize] = Some(info.default);
+            }
+        }
+        for info in &CONFIGURATION {
+            if supported(&|v| v.supports_configuration(info.id)) {
+                self.configuration[info.id as usize] = Some(info.default.unwrap_or(0));
+            }
+        }
+        for id in 0..CHANNEL_SLOTS as u8 {
+            if let Ok(ch) = Channels::try_from(id)
+                && supported(&|v| v.supports_channel(ch))
+            {
+                self.raw[id as usize] = Some(0.0);
+            }
+        }
+        self.configuration[ConfigurationCommands::DeviceAddress as usize] = Some(self.address);
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    pub fn identity(&self) -> Identity {
+        self.identity
+    }
+
+    pub fn serial_number(&self) -> u32 {
+        self.serial_number
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Simulates a power cycle: the device needs F48 again, reports the
+    /// restart in its next F48 reply, and drops anything in flight.
+    pub fn power_cycle(&mut self) {
+        self.initialized = false;
+        self.restarted = true;
+        self.tx_len = 0;
+    }
+
+    pub fn coefficient(&self, coefficient: Coefficients) -> Option<f32> {
+        self.coefficients[coefficient as usize]
+    }
+
+    /// Sets a coefficient; `None` makes the device reject it as unknown.
+    pub fn set_coefficient(&mut self, coefficient: Coefficients, value: Option<f32>) {
+        self.coefficients[coefficient as usize] = value;
+    }
+
+    pub fn configuration(&self, variable: ConfigurationCommands) -> Option<u8> {
+        self.configuration[variable as usize]
+    }
+
+    /// Sets a configuration entry; `None` makes the device reject it as unknown.
+    pub fn set_configuration(&mut self, variable: ConfigurationCommands, value: Option<u8>) {
+        self.configuration[variable as usize] = value;
+    }
+
+    /// Sets the uncorrected value a channel measures; `None` removes the
+    /// channel from the device.
+    pub fn set_raw(&mut self, channel: Channels, value: Option<f32>) {
+        self.raw[channel as usize] = value;
+    }
+
+    /// Value the device reports for `channel`, with offset and gain applied.
+    pub fn channel_value(&self, channel: Channels) -> Option<f32> {
+        let raw = self.raw[channel as usize]?;
+        Some(raw * self.gain(channel) + self.offset(channel))
+    }
+
+    /// Status byte appended to F73/F74 replies.
+    pub fn set_status(&mut self, status: u8) {
+        self.status = status;
+    }
+
+    /// Makes every write answer with [`KellerErrors::ErrorSavingValue`]
+    /// without storing anything.
+    pub fn set_fail_saves(&mut self, fail: bool) {
+        self.fail_saves = fail;
+    }
+
+    /// Number of values committed to (emulated) EEPROM so far.
+    pub fn eeprom_writes(&self) -> u32 {
+        self.eeprom_writes
+    }
+
+    fn offset_coefficient(&self, channel: Channels) -> Option<Coefficients> {
+        // ID 72 is a switching threshold, not the T offset, on v5.20.
+        if channel == Channels::T && self.identity.group == 20 {
+            return None;
+        }
+        channel.offset_coefficient()
+    }
+
+    fn offset(&self, channel: Channels) -> f32 {
+        self.offset_coefficient(channel)
+            .and_then(|c| self.coefficients[c as usize])
+            .unwrap_or(0.0)
+    }
+
+    fn gain(&self, channel: Channels) -> f32 {
+        channel
+            .gain_coefficient()
+            .and_then(|c| self.coefficients[c as usize])
+            .unwrap_or(1.0)
+    }
+
+    /// Handles one request frame and writes the reply into `reply`,
+    /// returning its length. Frames that are garbled or addressed to another
+    /// device get no reply (0), as on a real bus.
+    pub fn process(&mut self, request: &[u8], reply: &mut [u8]) -> usize {
+        let frame = match XLineFrame::from_buffer(request) {
+            Ok(frame) => frame,
+            Err(XLineFrameError::UnknownFunction(func)) if self.addressed(request[0]) => {
+                return self.exception(func, KellerErrors::NonImplementedFunction, reply);
+            }
+            Err(_) => return 0,
+        };
+        if !self.addressed(frame.address) {
+            return 0;
+        }
+        let func = frame.function_code;
+        if !func.request_lens().contains(&request.len()) {
+            return self.exception(func as u8, KellerErrors::IncorrectMessageLength, reply);
+        }
+        if !self.initialized && func != FunctionCodes::InitializeAndRealese {
+            return self.exception(func as u8, KellerErrors::DeviceNotInitialized, reply);
+        }
+        match self.handle(func, &frame.data) {
+            Ok(Reply {
+                address,
+                payload,
+                len,
+            }) => Self::encode(address, func as u8, &payload[..len], reply),
+            Err(error) => self.exception(func as u8, error, reply),
+        }
+    }
+
+    fn addressed(&self, address: u8) -> bool {
+        address == self.address || address == TRANSPARENT_ADDRESS
+    }
+
+    fn handle(&mut self, func: FunctionCodes, data: &[u8]) -> Result<Reply, KellerErrors> {
+        let address = self.address;
+        match func {
+            FunctionCodes::ReadCoefficients => {
+                let value =
+                    self.coefficients[data[0] as usize].ok_or(KellerErrors::InvalidAddress)?;
+                Ok(Reply::new(address, &value.to_be_bytes()))
+            }
+            FunctionCodes::WriteCoefficients => {
+                let id = data[0] as usize;
+                if self.coefficients[id].is_none() {
+                    return Err(KellerErrors::InvalidAddress);
+                }
+                let value =
+                    f32_from_be_bytes(&data[1..5]).ok_or(KellerErrors::IncorrectMessageLength)?;
+                self.save()?;
+                self.coefficients[id] = Some(value);
+                Ok(Reply::new(address, &[0]))
+            }
+            FunctionCodes::ReadConfigurations => {
+                let value =
+                    self.configuration[data[0] as usize].ok_or(KellerErrors::InvalidAddress)?;
+                Ok(Reply::new(address, &[value, 0, 0, 0]))
+            }
+            FunctionCodes::WriteConfiguration => {
+                let id = data[0] as usize;
+                if self.configuration[id].is_none() {
+                    return Err(KellerErrors::InvalidAddress);
+                }
+                self.save()?;
+                self.configuration[id] = Some(data[1]);
+                if id == ConfigurationCommands::DeviceAddress as usize {
+                    self.address = data[1];
+                }
+                Ok(Reply::new(address, &[0]))
+            }
+            FunctionCodes::InitializeAndRealese => {
+                let mut identity = self.identity;
+                identity.status = self.restarted as u8;
+                self.initialized = true;
+                self.restarted = false;
+                Ok(Reply::new(address, &identity.to_payload()))
+            }
+            FunctionCodes::WriteAndReadNewDeviceAddress => {
+                if let Some(&new_address) = data.first().filter(|a| **a != 0) {
+                    if new_address == TRANSPARENT_ADDRESS {
+                        return Err(KellerErrors::InvalidAddress);
+                    }
+                    self.save()?;
+                    self.address = new_address;
+                    self.configuration[ConfigurationCommands::DeviceAddress as usize] =
+                        Some(new_address);
+                }
+                Ok(Reply::new(address, &[self.address]))
+            }
+            FunctionCodes::ReadSerialNumber => {
+                Ok(Reply::new(address, &self.serial_number.to_be_bytes()))
+            }
+            FunctionCodes::ReadChannelValueFloat => {
+                let channel =
+                    Channels::try_from(data[0]).map_err(|_| KellerErrors::InvalidAddress)?;
+                let value = self
+                    .channel_value(channel)
+                    .ok_or(KellerErrors::InvalidAddress)?;
+                let be = value.to_be_bytes();
+                Ok(Reply::new(
+                    address,
+                    &[be[0], be[1], be[2], be[3], self.status],
+                ))
+            }
+            FunctionCodes::ReadChannelValueInteger => {
+                let channel =
+                    Channels::try_from(data[0]).map_err(|_| KellerErrors::InvalidAddress)?;
+                let value = self
+                    .channel_value(channel)
+                    .ok_or(KellerErrors::InvalidAddress)?;
+                let be = ((value * integer_scale(channel)) as i32).to_be_bytes();
+                Ok(Reply::new(
+                    address,
+                    &[be[0], be[1], be[2], be[3], self.status],
+                ))
+            }
+            FunctionCodes::ZeroCommand => {
+                let command =
+                    ZeroCommands::try_from(data[0]).map_err(|_| KellerErrors::InvalidAddress)?;
+                let channel = command.channel();
+                let coefficient = self
+                    .offset_coefficient(channel)
+                    .filter(|c| self.coefficients[*c as usize].is_some())
+                    .ok_or(KellerErrors::InvalidAddress)?;
+                let raw = self.raw[channel as usize].ok_or(KellerErrors::InvalidAddress)?;
+                let offset = if command.is_set() {
+                    let target = f32_from_be_bytes(data.get(1..5).unwrap_or(&[])).unwrap_or(0.0);
+                    target - raw * self.gain(channel)
+                } else {
+                    0.0
+                };
+                self.save()?;
+                self.coefficients[coefficient as usize] = Some(offset);
+                Ok(Reply::new(address, &[0]))
+            }
+            FunctionCodes::ReadConfigurationBlock => {
+                // Block `n` mirrors configuration registers 5n..5n+4.
+                let start = data[0] as usize * 5;
+                if start + 5 > self.configuration.len() {
+                    return Err(KellerErrors::InvalidAddress);
+                }
+                let mut block = [0u8; 5];
+                for (slot, value) in block.iter_mut().zip(&self.configuration[start..start + 5]) {
+                    *slot = value.unwrap_or(0);
+                }
+                Ok(Reply::new(address, &block))
+            }
+        }
+    }
+
+    fn save(&mut self) -> Result<(), KellerErrors> {
+        if self.fail_saves {
+            return Err(KellerErrors::ErrorSavingValue);
+        }
+        self.eeprom_writes += 1;
+        Ok(())
+    }
+
+    fn exception(&self, func: u8, error: KellerErrors, reply: &mut [u8]) -> usize {
+        Self::encode(self.address, func | 0x80, &[error.into()], reply)
+    }
+
+    fn encode(address: u8, func: u8, payload: &[u8], reply: &mut [u8]) -> usize {
+        let len = 2 + payload.len();
+        reply[0] = address;
+        reply[1] = func;
+        reply[2..len].copy_from_slice(payload);
+        let (hi, lo) = crc16_hi_lo(&reply[..len]);
+        reply[len] = hi;
+        reply[len + 1] = lo;
+        len + 2
+    }
+}
+
+/// Scale from engineering units to F74 integer counts: Pa, 0.01 °C, µS/cm.
+fn integer_scale(channel: Channels) -> f32 {
+    match channel {
+        Channels::CH0 | Channels::P1 | Channels::P2 => 100_000.0,
+        Channels::T | Channels::TOB1 | Channels::TOB2 => 100.0,
+        Channels::ConTc | Channels::ConRaw => 1000.0,
+    }
+}
+
+struct Reply {
+    address: u8,
+    payload: [u8; MAX_REPLY],
+    len: usize,
+}
+
+impl Reply {
+    fn new(address: u8, data: &[u8]) -> Self {
+        let mut payload = [0u8; MAX_REPLY];
+        payload[..data.len()].copy_from_slice(data);
+        Self {
+            address,
+            payload,
+            len: data.len(),
+        }
+    }
+}
+
+impl XLineIO for XLineDevice {
+    type Error = EmulatorError;
+
+    /// Treats every write as one complete request frame and queues the reply.
+    async fn write_all(&mut self, buf: &[u8], _timeout: Duration) -> Result<(), Self::Error> {
+        let mut reply = [0u8; MAX_REPLY];
+        let len = self.process(buf, &mut reply);
+        let room = TX_CAP - self.tx_len;
+        let len = len.min(room);
+        self.tx[self.tx_len..self.tx_len + len].copy_from_slice(&reply[..len]);
+        self.tx_len += len;
+        Ok(())
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<(), Self::Error> {
+        let n = buf.len().min(self.tx_len);
+        buf[..n].copy_from_slice(&self.tx[..n]);
+        self.tx.copy_within(n..self.tx_len, 0);
+        self.tx_len -= n;
+        if n < buf.len() {
+            return Err(EmulatorError::Timeout);
+        }
+        Ok(())
+    }
+
+    async fn clear_rx(&mut self) -> Result<(), Self::Error> {
+        self.tx_len = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmulatorError, XLineDevice};
+    use crate::KellerXLine;
+    use crate::base::{
+        Channels, Coefficients, ConfigurationCommands, KellerErrors, ProtocolError,
+        XLineFrameError, ZeroCommands, crc16_hi_lo,
+    };
+    use crate::testing::{TIMEOUT, block_on, client, identity};
+
+    fn device_error<R>(result: Result<R, ProtocolError<EmulatorError>>) -> KellerErrors {
+        match result {
+            Err(ProtocolError::FrameError(XLineFrameError::DeviceError(e))) => e,
+            Err(other) => panic!("expected a device error, got {other:?}"),
+            Ok(_) => panic!("expected a device error, got a reply"),
+        }
+    }
+
+    /// Feeds `request` (CRC appended) straight to the device.
+    fn raw(device: &mut XLineDevice, request: &[u8]) -> ([u8; 16], usize) {
+        let mut frame = [0u8; 16];
+        frame[..request.len()].copy_from_slice(request);
+        let (hi, lo) = crc16_hi_lo(request);
+        frame[request.len()] = hi;
+        frame[request.len() + 1] = lo;
+        let mut reply = [0u8; 16];
+        let len = device.process(&frame[..request.len() + 2], &mut reply);
+        (reply, len)
+    }
+
+    #[test]
+    fn f48_reports_identity_and_restart_once() {
+        let device = XLineDevice::new(1, identity(24), 1);
+        let xline = KellerXLine::new(device, TIMEOUT, 1).unwrap();
+        let mut xline = block_on(xline.init()).map_err(|e| e.error).unwrap();
+        assert_eq!(xline.identity().group, 24);
+        assert!(xline.identity().was_restarted());
+        let again = block_on(xline.init_and_release()).unwrap();
+        assert!(!again.was_restarted());
+        assert!(xline.transport_mut().is_initialized());
+    }
+
+    #[test]
+    fn requests_before_f48_are_refused() {
+        let mut xline = client(24);
+        xline.set_auto_reinit(false);
+        xline.transport_mut().power_cycle();
+        assert_eq!(
+            device_error(block_on(xline.read_serial_number())),
+            KellerErrors::DeviceNotInitialized
+        );
+    }
+
+    #[test]
+    fn f73_applies_gain_and_offset() {
+        let mut xline = client(24);
+        let device = xline.transport_mut();
+        device.set_raw(Channels::P1, Some(2.0));
+        device.set_coefficient(Coefficients::GainFactorP1, Some(1.5));
+        device.set_coefficient(Coefficients::PressureOffsetP1, Some(0.25));
+        device.set_status(0x01);
+        let (value, status) = block_on(xline.read_channel_value_and_status(Channels::P1)).unwrap();
+        assert_eq!(value, 3.25);
+        assert_eq!(status.bits(), 0x01);
+    }
+
+    #[test]
+    fn f73_rejects_a_missing_channel() {
+        let mut xline = client(24);
+        xline.set_firmware(None);
+        xline.transport_mut().set_raw(Channels::ConTc, None);
+        assert_eq!(
+            device_error(block_on(xline.read_channel_value(Channels::ConTc))),
+            KellerErrors::InvalidAddress
+        );
+    }
+
+    #[test]
+    fn f30_f31_round_trip_a_coefficient() {
+        let mut xline = client(24);
+        block_on(xline.write_coefficent(Coefficients::Free100, 12.5)).unwrap();
+        assert_eq!(
+            block_on(xline.read_coefficent(Coefficients::Free100)).unwrap(),
+            12.5
+        );
+        assert_eq!(
+            xline.transport_mut().coefficient(Coefficients::Free100),
+            Some(12.5)
+        );
+        assert_eq!(xline.transport_mut().eeprom_writes(), 1);
+    }
+
+    #[test]
+    fn f30_rejects_an_unknown_coefficient() {
+        let mut xline = client(24);
+        xline.set_firmware(None);
+        xline
+            .transport_mut()
+            .set_coefficient(Coefficients::Free111, None);
+        assert_eq!(
+            device_error(block_on(xline.read_coefficent(Coefficients::Free111))),
+            KellerErrors::InvalidAddress
+        );
+    }
+
+    #[test]
+    fn f31_reports_a_failed_save() {
+        let mut xline = client(24);
+        xline.transport_mut().set_fail_saves(true);
+        assert_eq!(
+            device_error(block_on(
+                xline.write_coefficent(Coefficients::Free100, 1.0)
+            )),
+            KellerErrors::ErrorSavingValue
+        );
+        assert_eq!(
+            xline.transport_mut().coefficient(Coefficients::Free100),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn f32_f33_round_trip_a_configuration_entry() {
+        let mut xline = client(24);
+        block_on(xline.write_configuration(ConfigurationCommands::Filter, 3)).unwrap();
+        assert_eq!(
+            block_on(xline.read_configuration(ConfigurationCommands::Filter)).unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    fn f95_zeroes_and_resets_a_channel() {
+        let mut xline = client(24);
+        xline.transport_mut().set_raw(Channels::P1, Some(0.4));
+        block_on(xline.zero(ZeroCommands::SetZeroP1)).unwrap();
+        assert_eq!(block_on(xline.read_channel_value(Channels::P1)).unwrap(), 0.0);
+        block_on(xline.zero_with_value(ZeroCommands::SetZeroP1, 1.0)).unwrap();
+        assert_eq!(block_on(xline.read_channel_value(Channels::P1)).unwrap(), 1.0);
+        block_on(xline.zero(ZeroCommands::ResetZeroP1)).unwrap();
+        assert_eq!(block_on(xline.read_channel_value(Channels::P1)).unwrap(), 0.4);
+    }
+
+    #[test]
+    fn f66_moves_the_device_and_follows_it() {
+        let mut xline = client(24);
+        assert_eq!(block_on(xline.write_address(0)).unwrap(), 1);
+        assert_eq!(block_on(xline.write_address(7)).unwrap(), 7);
+        assert_eq!(xline.address(), 7);
+        assert_eq!(xline.transport_mut().address(), 7);
+        assert_eq!(
+            block_on(xline.read_configuration(ConfigurationCommands::DeviceAddress)).unwrap(),
+            7
+        );
+    }
+
+    #[test]
+    fn f66_refuses_the_transparent_address() {
+        // The client refuses 250 before sending, so talk to the device raw;
+        // exception code 2 is InvalidAddress.
+        let mut device = XLineDevice::new(1, identity(24), 1);
+        raw(&mut device, &[1, 48]);
+        let (reply, len) = raw(&mut device, &[1, 66, 250]);
+        assert_eq!(&reply[..3], &[1, 66 | 0x80, 2]);
+        assert_eq!(len, 5);
+        assert_eq!(device.address(), 1);
+    }
+
+    #[test]
+    fn reads_the_serial_number() {
+        let mut xline = client(24);
+        assert_eq!(block_on(xline.read_serial_number()).unwrap().0, 4_000_123);
+    }
+
+    #[test]
+    fn other_addresses_get_no_reply() {
+        let device = XLineDevice::new(9, identity(24), 1);
+        let xline = KellerXLine::new(device, TIMEOUT, 1).unwrap();
+        assert!(matches!(
+            block_on(xline.init()).err().unwrap().error,
+            ProtocolError::Transport(EmulatorError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn unknown_functions_and_bad_lengths_raise_exceptions() {
+        let mut device = XLineDevice::new(1, identity(24), 1);
+        let (reply, len) = raw(&mut device, &[1, 99]);
+        assert_eq!(&reply[..3], &[1, 99 | 0x80, 1]);
+        assert_eq!(len, 5);
+        let (reply, len) = raw(&mut device, &[1, 67, 0]);
+        assert_eq!(&reply[..3], &[1, 67 | 0x80, 3]);
+        assert_eq!(len, 5);
+    }
+
+    #[test]
+    fn garbled_requests_get_no_reply() {
+        let mut device = XLineDevice::new(1, identity(24), 1);
+        let mut reply = [0u8; 16];
+        assert_eq!(device.process(&[1, 48, 0, 0], &mut reply), 0);
+        assert_eq!(device.process(&[1], &mut reply), 0);
+    }
+}
@@ -0,0 +1,431 @@
+use core::time::Duration;
+
+use crate::base::{FunctionCodes, XLineIO, crc16_hi_lo};
+
+const FRAME_CAP: usize = 64;
+const SCRIPT_CAP: usize = 32;
+
+/// A failure injected into one request/response exchange.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Pass the reply through untouched.
+    None,
+    /// Flip bits in the reply's CRC.
+    CorruptCrc,
+    /// Lose one byte from the middle of the reply.
+    DropByte,
+    /// Cut the reply short.
+    Truncate,
+    /// Deliver the reply only after the read has timed out.
+    Late,
+    /// Deliver the reply twice.
+    Duplicate,
+    /// Answer from a different address (with a valid CRC).
+    WrongAddress,
+    /// Flip the lowest bit of the first payload byte (with a valid CRC), so
+    /// the reply decodes to a different value.
+    CorruptPayload,
+}
+
+impl Fault {
+    const ALL: [Fault; 7] = [
+        Fault::CorruptCrc,
+        Fault::DropByte,
+        Fault::Truncate,
+        Fault::Late,
+        Fault::Duplicate,
+        Fault::WrongAddress,
+        Fault::CorruptPayload,
+    ];
+}
+
+/// Per-exchange probability of each fault, in `0.0..=1.0`. At most one
+/// fault is injected per exchange.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct FaultRates {
+    pub corrupt_crc: f32,
+    pub drop_byte: f32,
+    pub truncate: f32,
+    pub late: f32,
+    pub duplicate: f32,
+    pub wrong_address: f32,
+    pub corrupt_payload: f32,
+}
+
+impl FaultRates {
+    fn rate(&self, fault: Fault) -> f32 {
+        match fault {
+            Fault::None => 0.0,
+            Fault::CorruptCrc => self.corrupt_crc,
+            Fault::DropByte => self.drop_byte,
+            Fault::Truncate => self.truncate,
+            Fault::Late => self.late,
+            Fault::Duplicate => self.duplicate,
+            Fault::WrongAddress => self.wrong_address,
+            Fault::CorruptPayload => self.corrupt_payload,
+        }
+    }
+}
+
+/// How many times each fault has been injected.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FaultStats {
+    pub exchanges: u32,
+    pub corrupt_crc: u32,
+    pub drop_byte: u32,
+    pub truncate: u32,
+    pub late: u32,
+    pub duplicate: u32,
+    pub wrong_address: u32,
+    pub corrupt_payload: u32,
+}
+
+impl FaultStats {
+    fn record(&mut self, fault: Fault) {
+        self.exchanges += 1;
+        match fault {
+            Fault::None => {}
+            Fault::CorruptCrc => self.corrupt_crc += 1,
+            Fault::DropByte => self.drop_byte += 1,
+            Fault::Truncate => self.truncate += 1,
+            Fault::Late => self.late += 1,
+            Fault::Duplicate => self.duplicate += 1,
+            Fault::WrongAddress => self.wrong_address += 1,
+            Fault::CorruptPayload => self.corrupt_payload += 1,
+        }
+    }
+}
+
+/// Wraps any [`XLineIO`] and corrupts replies on demand.
+///
+/// Faults come from a script queued with [`FaultInjector::script`] first and,
+/// once that runs dry, are drawn at random from [`FaultRates`] using a seeded
+/// generator so a failing run can be reproduced. The wrapper reads each
+/// reply from the inner transport in full (sizing it from the function
+/// code), applies the fault and then serves it to the caller; missing bytes
+/// are requested from the inner transport, so they fail exactly as a real
+/// timeout on that transport would.
+///
+/// Late and duplicated replies leave stale bytes behind. `clear_rx` discards
+/// them, as the std transports do, unless [`FaultInjector::set_keep_stale`]
+/// is on to model a transport that cannot flush its input.
+pub struct FaultInjector<T> {
+    inner: T,
+    rates: FaultRates,
+    rng: u32,
+    script: [Fault; SCRIPT_CAP],
+    script_len: usize,
+    script_pos: usize,
+    keep_stale: bool,
+    awaiting_reply: bool,
+    rx: [u8; FRAME_CAP],
+    rx_len: usize,
+    stats: FaultStats,
+}
+
+impl<T: XLineIO> FaultInjector<T> {
+    /// Wraps `inner` with no faults configured; `seed` drives the random
+    /// draws and must be non-zero.
+    pub fn new(inner: T, seed: u32) -> Self {
+        Self {
+            inner,
+            rates: FaultRates::default(),
+            rng: seed.max(1),
+            script: [Fault::None; SCRIPT_CAP],
+            script_len: 0,
+            script_pos: 0,
+            keep_stale: false,
+            awaiting_reply: false,
+            rx: [0; FRAME_CAP],
+            rx_len: 0,
+            stats: FaultStats::default(),
+        }
+    }
+
+    pub fn set_rates(&mut self, rates: FaultRates) {
+        self.rates = rates;
+    }
+
+    /// Replaces the scripted sequence; one entry is consumed per exchange.
+    /// Entries beyond the script capacity (32) are ignored.
+    pub fn script(&mut self, faults: &[Fault]) {
+        let len = faults.len().min(SCRIPT_CAP);
+        self.script[..len].copy_from_slice(&faults[..len]);
+        self.script_len = len;
+        self.script_pos = 0;
+    }
+
+    pub fn set_keep_stale(&mut self, keep: bool) {
+        self.keep_stale = keep;
+    }
+
+    pub fn stats(&self) -> FaultStats {
+        self.stats
+    }
+
+    pub fn inner(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn next_random(&mut self) -> u32 {
+        // xorshift32
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        x
+    }
+
+    fn next_fault(&mut self) -> Fault {
+        if self.script_pos < self.script_len {
+            let fault = self.script[self.script_pos];
+            self.script_pos += 1;
+            return fault;
+        }
+        let draw = (self.next_random() >> 8) as f32 / (1u32 << 24) as f32;
+        let mut acc = 0.0;
+        for fault in Fault::ALL {
+            acc += self.rates.rate(fault);
+            if draw < acc {
+                return fault;
+            }
+        }
+        Fault::None
+    }
+
+    fn queue(&mut self, bytes: &[u8]) {
+        let len = bytes.len().min(FRAME_CAP - self.rx_len);
+        self.rx[self.rx_len..self.rx_len + len].copy_from_slice(&bytes[..len]);
+        self.rx_len += len;
+    }
+
+    /// Pulls the complete reply to the last request out of the inner
+    /// transport and queues it with a fault applied. Returns `true` when the
+    /// reply is late and the current read must time out.
+    async fn fetch_reply(&mut self, timeout: Duration) -> Result<bool, T::Error> {
+        let mut frame = [0u8; FRAME_CAP];
+        self.inner.read_exact(&mut frame[..2], timeout).await?;
+        let len = if frame[1] > 127 {
+            5
+        } else {
+            FunctionCodes::try_from(frame[1])
+                .map(|f| f.response_len())
+                .unwrap_or(2)
+        };
+        self.inner.read_exact(&mut frame[2..len], timeout).await?;
+
+        let fault = self.next_fault();
+        self.stats.record(fault);
+        if fault != Fault::None && len < 4 {
+            self.queue(&frame[..len]);
+            return Ok(false);
+        }
+        match fault {
+            Fault::None | Fault::Late => self.queue(&frame[..len]),
+            Fault::CorruptCrc => {
+                frame[len - 1] ^= 0xA5;
+                self.queue(&frame[..len]);
+            }
+            Fault::DropByte => {
+                let at = 2 + self.next_random() as usize % (len - 2);
+                frame.copy_within(at + 1..len, at);
+                self.queue(&frame[..len - 1]);
+            }
+            Fault::Truncate => {
+                let keep = 2 + self.next_random() as usize % (len - 3);
+                self.queue(&frame[..keep]);
+            }
+            Fault::Duplicate => {
+                self.queue(&frame[..len]);
+                self.queue(&frame[..len]);
+            }
+            Fault::WrongAddress => {
+                frame[0] = frame[0].wrapping_add(1);
+                resign(&mut frame[..len]);
+                self.queue(&frame[..len]);
+            }
+            Fault::CorruptPayload => {
+                // Replies without a payload pass through.
+                if len > 4 {
+                    frame[2] ^= 0x01;
+                    resign(&mut frame[..len]);
+                }
+                self.queue(&frame[..len]);
+            }
+        }
+        Ok(fault == Fault::Late)
+    }
+}
+
+/// Recomputes the CRC of a complete frame after its contents changed.
+fn resign(frame: &mut [u8]) {
+    let len = frame.len();
+    let (hi, lo) = crc16_hi_lo(&frame[..len - 2]);
+    frame[len - 2] = hi;
+    frame[len - 1] = lo;
+}
+
+impl<T: XLineIO> XLineIO for FaultInjector<T> {
+    type Error = T::Error;
+
+    async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Self::Error> {
+        self.inner.write_all(buf, timeout).await?;
+        self.awaiting_reply = true;
+        Ok(())
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), Self::Error> {
+        if self.awaiting_reply {
+            self.awaiting_reply = false;
+            if self.fetch_reply(timeout).await? {
+                // Nothing is left in the inner transport, so this times out
+                // the way the real one would.
+                return self.inner.read_exact(buf, timeout).await;
+            }
+        }
+        let n = buf.len().min(self.rx_len);
+        buf[..n].copy_from_slice(&self.rx[..n]);
+        self.rx.copy_within(n..self.rx_len, 0);
+        self.rx_len -= n;
+        if n < buf.len() {
+            return self.inner.read_exact(&mut buf[n..], timeout).await;
+        }
+        Ok(())
+    }
+
+    async fn clear_rx(&mut self) -> Result<(), Self::Error> {
+        if !self.keep_stale {
+            self.rx_len = 0;
+        }
+        self.inner.clear_rx().await
+    }
+
+    async fn flush(&mut self, timeout: Duration) -> Result<(), Self::Error> {
+        self.inner.flush(timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fault, FaultInjector};
+    use crate::KellerXLine;
+    use crate::base::{Coefficients, ProtocolError, XLineFrameError};
+    use crate::emulator::{EmulatorError, XLineDevice};
+    use crate::testing::{TIMEOUT, block_on, identity};
+
+    /// Initialised client whose first request after F48 suffers `fault`.
+    fn faulty(fault: Fault) -> KellerXLine<FaultInjector<XLineDevice>> {
+        let device = XLineDevice::new(1, identity(24), 4_000_123);
+        let mut injector = FaultInjector::new(device, 1);
+        injector.script(&[Fault::None, fault]);
+        let xline = KellerXLine::new(injector, TIMEOUT, 1).unwrap();
+        block_on(xline.init()).map_err(|e| e.error).unwrap()
+    }
+
+    fn is_timeout<R>(result: Result<R, ProtocolError<EmulatorError>>) -> bool {
+        matches!(result, Err(ProtocolError::Transport(EmulatorError::Timeout)))
+    }
+
+    #[test]
+    fn corrupt_crc_is_a_bad_crc() {
+        let mut xline = faulty(Fault::CorruptCrc);
+        assert!(matches!(
+            block_on(xline.read_serial_number()),
+            Err(ProtocolError::FrameError(XLineFrameError::BadCrc { .. }))
+        ));
+        assert_eq!(xline.transport_mut().stats().corrupt_crc, 1);
+    }
+
+    #[test]
+    fn dropped_byte_times_out() {
+        let mut xline = faulty(Fault::DropByte);
+        assert!(is_timeout(block_on(xline.read_serial_number())));
+        assert_eq!(xline.transport_mut().stats().drop_byte, 1);
+    }
+
+    #[test]
+    fn truncated_reply_times_out() {
+        let mut xline = faulty(Fault::Truncate);
+        assert!(is_timeout(block_on(xline.read_serial_number())));
+        assert_eq!(xline.transport_mut().stats().truncate, 1);
+    }
+
+    #[test]
+    fn late_reply_times_out_and_is_discarded() {
+        let mut xline = faulty(Fault::Late);
+        assert!(is_timeout(block_on(xline.read_serial_number())));
+        let value = block_on(xline.read_coefficent(Coefficients::GainFactorP1)).unwrap();
+        assert_eq!(value, 1.0);
+    }
+
+    #[test]
+    fn late_reply_kept_stale_answers_the_next_request() {
+        let mut xline = faulty(Fault::Late);
+        xline.transport_mut().set_keep_stale(true);
+        assert!(is_timeout(block_on(xline.read_serial_number())));
+        assert!(matches!(
+            block_on(xline.read_coefficent(Coefficients::GainFactorP1)),
+            Err(ProtocolError::NonMatchingFunctionCode)
+        ));
+    }
+
+    #[test]
+    fn duplicate_reply_is_discarded() {
+        let mut xline = faulty(Fault::Duplicate);
+        assert_eq!(block_on(xline.read_serial_number()).unwrap().0, 4_000_123);
+        let value = block_on(xline.read_coefficent(Coefficients::GainFactorP1)).unwrap();
+        assert_eq!(value, 1.0);
+        assert_eq!(xline.transport_mut().stats().duplicate, 1);
+    }
+
+    #[test]
+    fn duplicate_reply_kept_stale_answers_the_next_request() {
+        let mut xline = faulty(Fault::Duplicate);
+        xline.transport_mut().set_keep_stale(true);
+        block_on(xline.read_serial_number()).unwrap();
+        assert!(matches!(
+            block_on(xline.read_coefficent(Coefficients::GainFactorP1)),
+            Err(ProtocolError::NonMatchingFunctionCode)
+        ));
+    }
+
+    #[test]
+    fn wrong_address_is_rejected() {
+        let mut xline = faulty(Fault::WrongAddress);
+        assert!(matches!(
+            block_on(xline.read_serial_number()),
+            Err(ProtocolError::WrongAddress)
+        ));
+        assert_eq!(xline.transport_mut().stats().wrong_address, 1);
+    }
+
+    #[test]
+    fn rates_are_reproducible_from_the_seed() {
+        let run = |seed| {
+            let device = XLineDevice::new(1, identity(24), 1);
+            let mut injector = FaultInjector::new(device, seed);
+            injector.set_rates(super::FaultRates {
+                corrupt_crc: 0.2,
+                late: 0.2,
+                ..Default::default()
+            });
+            injector.script(&[Fault::None]);
+            let xline = KellerXLine::new(injector, TIMEOUT, 1).unwrap();
+            let mut xline = block_on(xline.init()).ok().unwrap();
+            for _ in 0..50 {
+                let _ = block_on(xline.read_serial_number());
+            }
+            xline.transport_mut().stats()
+        };
+        assert_eq!(run(7), run(7));
+        assert!(run(7).corrupt_crc > 0);
+    }
+}
@@ -1,36 +1,228 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 pub mod base;
+pub mod cache;
+pub mod calibration;
+#[cfg(feature = "std")]
+pub mod capture;
+pub mod catalog;
+#[cfg(feature = "unstable-curve")]
+pub mod curve;
+pub mod emulator;
+pub mod fault;
+#[cfg(feature = "std")]
+pub mod linearity;
+pub mod poll;
+#[cfg(feature = "std")]
+pub mod profile;
+pub mod registry;
+pub mod rs485;
+#[cfg(feature = "std")]
+pub mod serial;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod sniffer;
+pub mod stability;
+#[cfg(feature = "std")]
+pub mod tcp;
+#[cfg(test)]
+mod testing;
+pub mod units;
+pub mod verify;
+pub mod wear;
+pub mod zero;
 
 use crate::base::{
-    Channels, Coefficients, ConfigurationCommands, ProtocolError, XLineFrame, XLineIO,
+    ChannelStatus, Channels, Coefficients, ConfigurationCommands, DeviceInfo, Identity,
+    KellerErrors, ProtocolError, SerialNumber, XLineFrame, XLineFrameError, XLineIO,
     XLineResponseFrame, ZeroCommands, crc16_hi_lo,
 };
+use crate::catalog::{FirmwareVariant, UnsupportedEntry};
+use crate::verify::{WriteVerify, WrittenValue};
+use crate::wear::WearGuard;
 use core::time::Duration;
 #[cfg(feature = "std")]
 type Bytes = std::vec::Vec<u8>;
 #[cfg(all(not(feature = "std"), feature = "embedded"))]
 /// 250 is the maximum number of bytes so that will be allocated for each frame
 const BYTES_CAP: usize = 250;
+#[cfg(all(not(feature = "std"), feature = "embedded"))]
 type Bytes = heapless::Vec<u8, BYTES_CAP>;
 
 pub const TRANSPARENT_ADDRESS: u8 = 250;
+/// Length of an exception reply: addr, func | 0x80, error code, CRC.
+const EXCEPTION_LEN: usize = 5;
 
-pub struct KellerXLine<T: XLineIO> {
+/// Client for one X-line bus, addressing one device at a time.
+///
+/// `S` tracks whether the device has been initialised with F48:
+/// [`KellerXLine::new`] returns an [`Uninitialized`] handle that can only
+/// [`KellerXLine::init`], which yields an [`Initialized`] handle with the
+/// full API.
+pub struct KellerXLine<T: XLineIO, S = Initialized> {
     transport: T,
     timeout: Duration,
     address: u8,
+    firmware: Option<FirmwareVariant>,
+    verify: WriteVerify,
+    wear: WearGuard,
+    auto_reinit: bool,
+    restarts: u32,
+    event: Option<XLineEvent>,
+    state: S,
+}
+
+/// State of a [`KellerXLine`] that has not initialised its device yet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Uninitialized;
+
+/// State of a [`KellerXLine`] whose device answered F48: the address it
+/// answered on and the identity it reported.
+///
+/// It can only be obtained from [`KellerXLine::init`], so it always pairs an
+/// identity with the device that reported it. Keep a copy per device (see
+/// [`KellerXLine::state`]) to move one handle between known devices with
+/// [`KellerXLine::switch_to`] or [`KellerXLine::resume`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Initialized {
+    address: u8,
+    identity: Identity,
+}
+
+impl Initialized {
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    pub fn identity(&self) -> Identity {
+        self.identity
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for super::Uninitialized {}
+    impl Sealed for super::Initialized {}
+}
+
+/// [`Uninitialized`] or [`Initialized`].
+pub trait DeviceState: sealed::Sealed {
+    #[doc(hidden)]
+    fn identified(&mut self, identity: Identity);
+}
+
+impl DeviceState for Uninitialized {
+    fn identified(&mut self, _identity: Identity) {}
+}
+
+impl DeviceState for Initialized {
+    fn identified(&mut self, identity: Identity) {
+        self.identity = identity;
+    }
+}
+
+/// [`KellerXLine::init`] failed; the handle is handed back for a retry.
+pub struct InitError<T: XLineIO> {
+    pub xline: KellerXLine<T, Uninitialized>,
+    pub error: ProtocolError<T::Error>,
+}
+
+/// Something the client noticed about the device while handling a request.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum XLineEvent {
+    /// The device had lost its initialisation (typically a power cycle)
+    /// and was re-initialised; anything cached about it may be stale.
+    DeviceRestarted { address: u8, identity: Identity },
 }
 
 pub type XLineResult<T, E> = Result<T, ProtocolError<E>>;
 
-impl<T: XLineIO> KellerXLine<T> {
-    pub fn new(transport: T, timeout: Duration, address: u8) -> XLineResult<Self, T::Error> {
-        Ok(Self {
-            transport,
-            timeout,
-            address,
-        })
+impl<T: XLineIO, S: DeviceState> KellerXLine<T, S> {
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Points the handle at a device identified earlier, without another
+    /// F48. If that device has restarted since, its next request re-runs F48
+    /// as usual.
+    pub fn resume(mut self, device: Initialized) -> KellerXLine<T> {
+        self.address = device.address;
+        self.firmware = FirmwareVariant::from_identity(&device.identity);
+        self.into_state(device)
+    }
+
+    /// Firmware variant of the addressed device, as learned from F48.
+    pub fn firmware(&self) -> Option<FirmwareVariant> {
+        self.firmware
+    }
+
+    /// Overrides the firmware variant used to reject unsupported requests;
+    /// `None` lets every request through.
+    pub fn set_firmware(&mut self, firmware: Option<FirmwareVariant>) {
+        self.firmware = firmware;
+    }
+
+    pub fn write_verify(&self) -> WriteVerify {
+        self.verify
+    }
+
+    /// Makes every coefficient and configuration write read the entry back
+    /// and fail with [`ProtocolError::VerifyMismatch`] if it differs.
+    pub fn set_write_verify(&mut self, verify: WriteVerify) {
+        self.verify = verify;
+    }
+
+    /// Whether a [`KellerErrors::DeviceNotInitialized`] reply triggers F48
+    /// and one retry (the default).
+    pub fn set_auto_reinit(&mut self, enabled: bool) {
+        self.auto_reinit = enabled;
+    }
+
+    /// Takes the most recent event, if any happened since the last call.
+    pub fn take_event(&mut self) -> Option<XLineEvent> {
+        self.event.take()
+    }
+
+    /// Number of automatic re-initialisations so far.
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    fn check_write_budget(&self) -> XLineResult<(), T::Error> {
+        match self.wear.budget {
+            Some(limit) if self.wear.remaining(self.address) == Some(0) => {
+                Err(ProtocolError::WriteBudgetExceeded { limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_supported(&self, entry: UnsupportedEntry) -> XLineResult<(), T::Error> {
+        let Some(firmware) = self.firmware else {
+            return Ok(());
+        };
+        let supported = match entry {
+            UnsupportedEntry::Coefficient(c) => firmware.supports_coefficient(c),
+            UnsupportedEntry::Channel(c) => firmware.supports_channel(c),
+            UnsupportedEntry::Configuration(c) => firmware.supports_configuration(c),
+            UnsupportedEntry::Zero(c) => firmware.supports_zero(c),
+        };
+        if supported {
+            Ok(())
+        } else {
+            Err(ProtocolError::Unsupported(entry))
+        }
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
     }
 
     async fn send_frame(&mut self, frame: &XLineFrame) -> XLineResult<(), T::Error> {
@@ -40,20 +232,22 @@ impl<T: XLineIO> KellerXLine<T> {
             let mut v = Bytes::with_capacity(2 + frame.data.len() + 2);
             v.push(frame.address);
             v.push(frame.function_code as u8);
-            v.extend_from_slice(frame.data);
+            v.extend_from_slice(&frame.data);
             v
         };
         #[cfg(all(not(feature = "std"), feature = "embedded"))]
         let mut out: Bytes = {
             let mut v = Bytes::new();
-            let _ = v.push(self.address);
+            let _ = v.push(frame.address);
             let _ = v.push(frame.function_code as u8);
             let _ = v.extend_from_slice(frame.data.as_slice());
             v
         };
         let (hi, lo) = crc16_hi_lo(&out);
-        let _ = out.push(hi);
-        let _ = out.push(lo);
+        #[cfg(feature = "std")]
+        out.extend_from_slice(&[hi, lo]);
+        #[cfg(all(not(feature = "std"), feature = "embedded"))]
+        let _ = out.extend_from_slice(&[hi, lo]);
         self.transport.write_all(&out, self.timeout).await?;
         Ok(())
     }
@@ -63,32 +257,65 @@ impl<T: XLineIO> KellerXLine<T> {
         expected_len: usize,
     ) -> XLineResult<XLineResponseFrame, T::Error> {
         #[cfg(feature = "std")]
-        let mut raw = vec![0u8; expected_len];
+        let mut raw = vec![0u8; expected_len.max(EXCEPTION_LEN)];
         #[cfg(all(not(feature = "std"), feature = "embedded"))]
         let mut raw = [0u8; BYTES_CAP];
 
-        #[cfg(feature = "std")]
-        self.transport.read_exact(&mut raw, self.timeout).await?;
-        #[cfg(all(not(feature = "std"), feature = "embedded"))]
+        // Exception replies are five bytes whatever the function. Reading
+        // `expected_len` bytes in one go would wait out the timeout on a
+        // shorter exception and report a timeout instead of the device's
+        // error, so look at the function code before reading the rest.
+        self.transport
+            .read_exact(&mut raw[..2], self.timeout)
+            .await?;
+        let len = if raw[1] > 127 {
+            EXCEPTION_LEN
+        } else {
+            expected_len
+        };
         self.transport
-            .read_exact(&mut raw[..expected_len], self.timeout)
+            .read_exact(&mut raw[2..len], self.timeout)
             .await?;
 
-        #[cfg(feature = "std")]
         let parsed =
-            XLineResponseFrame::from_buffer(&raw).map_err(parse_err_to_proto::<T::Error>)?;
-        #[cfg(all(not(feature = "std"), feature = "embedded"))]
-        let parsed = XLineResponseFrame::from_buffer(&raw[..expected_len])
-            .map_err(ProtocolError::FrameError)?;
+            XLineResponseFrame::from_buffer(&raw[..len]).map_err(ProtocolError::FrameError)?;
         Ok(parsed)
     }
 
+    /// Sends `req` and reads its reply. A device that answers
+    /// [`KellerErrors::DeviceNotInitialized`] is re-initialised and asked
+    /// again once, and [`XLineEvent::DeviceRestarted`] is queued.
     async fn transaction(
         &mut self,
         req: XLineFrame,
         expected_reply_len: usize,
     ) -> XLineResult<XLineResponseFrame, T::Error> {
-        self.send_frame(&req).await?;
+        let result = self.exchange(&req, expected_reply_len).await;
+        if self.auto_reinit
+            && matches!(
+                result,
+                Err(ProtocolError::FrameError(XLineFrameError::DeviceError(
+                    KellerErrors::DeviceNotInitialized
+                )))
+            )
+        {
+            let identity = self.initialize().await?;
+            self.restarts = self.restarts.wrapping_add(1);
+            self.event = Some(XLineEvent::DeviceRestarted {
+                address: self.address,
+                identity,
+            });
+            return self.exchange(&req, expected_reply_len).await;
+        }
+        result
+    }
+
+    async fn exchange(
+        &mut self,
+        req: &XLineFrame,
+        expected_reply_len: usize,
+    ) -> XLineResult<XLineResponseFrame, T::Error> {
+        self.send_frame(req).await?;
         let resp = self.read_response(expected_reply_len).await?;
         if resp.function_code != req.function_code as u8 {
             return Err(ProtocolError::NonMatchingFunctionCode);
@@ -100,10 +327,109 @@ impl<T: XLineIO> KellerXLine<T> {
         Ok(resp)
     }
 
+    /// F48 without the re-initialisation retry of [`Self::transaction`].
+    async fn initialize(&mut self) -> XLineResult<Identity, T::Error> {
+        let req = XLineFrame {
+            address: self.address,
+            function_code: base::FunctionCodes::InitializeAndRealese,
+            data: [].into(),
+        };
+        let response = self
+            .exchange(
+                &req,
+                base::FunctionCodes::InitializeAndRealese.response_len(),
+            )
+            .await?;
+        let identity = Identity::from_payload(&response.payload)
+            .ok_or(ProtocolError::FrameError(XLineFrameError::TooShort))?;
+        self.firmware = FirmwareVariant::from_identity(&identity);
+        self.state.identified(identity);
+        Ok(identity)
+    }
+
+    fn into_state<N>(self, state: N) -> KellerXLine<T, N> {
+        KellerXLine {
+            transport: self.transport,
+            timeout: self.timeout,
+            address: self.address,
+            firmware: self.firmware,
+            verify: self.verify,
+            wear: self.wear,
+            auto_reinit: self.auto_reinit,
+            restarts: self.restarts,
+            event: self.event,
+            state,
+        }
+    }
+}
+
+impl<T: XLineIO> KellerXLine<T, Uninitialized> {
+    pub fn new(transport: T, timeout: Duration, address: u8) -> XLineResult<Self, T::Error> {
+        Ok(Self {
+            transport,
+            timeout,
+            address,
+            firmware: None,
+            verify: WriteVerify::Off,
+            wear: WearGuard::default(),
+            auto_reinit: true,
+            restarts: 0,
+            event: None,
+            state: Uninitialized,
+        })
+    }
+
+    /// Points subsequent requests at another device on the same bus. The
+    /// firmware variant is forgotten until [`KellerXLine::init`].
+    pub fn set_address(&mut self, address: u8) {
+        self.address = address;
+        self.firmware = None;
+    }
+
+    /// Initialises the device with F48.
+    #[allow(clippy::result_large_err)]
+    pub async fn init(mut self) -> Result<KellerXLine<T>, InitError<T>> {
+        match self.initialize().await {
+            Ok(identity) => {
+                let address = self.address;
+                Ok(self.into_state(Initialized { address, identity }))
+            }
+            Err(error) => Err(InitError { xline: self, error }),
+        }
+    }
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    /// Identity from the most recent F48.
+    pub fn identity(&self) -> Identity {
+        self.state.identity
+    }
+
+    /// The addressed device and its identity, for a later
+    /// [`KellerXLine::switch_to`] or [`KellerXLine::resume`].
+    pub fn state(&self) -> Initialized {
+        self.state
+    }
+
+    /// Points the handle at a device identified earlier, without another
+    /// F48; the by-reference form of [`KellerXLine::resume`].
+    pub fn switch_to(&mut self, device: Initialized) {
+        self.address = device.address;
+        self.firmware = FirmwareVariant::from_identity(&device.identity);
+        self.state = device;
+    }
+
+    /// Gives up the initialised state, e.g. to identify other devices on
+    /// the bus with [`KellerXLine::init`].
+    pub fn into_uninitialized(self) -> KellerXLine<T, Uninitialized> {
+        self.into_state(Uninitialized)
+    }
+
     pub async fn read_coefficent(
         &mut self,
         coefficient: Coefficients,
     ) -> XLineResult<f32, T::Error> {
+        self.check_supported(UnsupportedEntry::Coefficient(coefficient))?;
         let req = XLineFrame {
             address: self.address,
             function_code: base::FunctionCodes::ReadCoefficients,
@@ -115,10 +441,44 @@ impl<T: XLineIO> KellerXLine<T> {
         Ok(response.data_as_f32())
     }
 
+    /// Writes `value` after checking it against the coefficient's registry
+    /// constraints; see [`Self::force_write_coefficent`] to skip the check.
     pub async fn write_coefficent(
         &mut self,
         coefficient: Coefficients,
         value: f32,
+    ) -> XLineResult<(), T::Error> {
+        coefficient
+            .info()
+            .validate(value)
+            .map_err(ProtocolError::Validation)?;
+        self.force_write_coefficent(coefficient, value).await
+    }
+
+    /// Writes `value` as is, even NaN or a zero gain.
+    pub async fn force_write_coefficent(
+        &mut self,
+        coefficient: Coefficients,
+        value: f32,
+    ) -> XLineResult<(), T::Error> {
+        self.check_supported(UnsupportedEntry::Coefficient(coefficient))?;
+        if self.wear.skip_redundant
+            && self.read_coefficent(coefficient).await?.to_bits() == value.to_bits()
+        {
+            self.wear.record_skip(self.address);
+            return Ok(());
+        }
+        self.check_write_budget()?;
+        self.write_coefficent_unchecked(coefficient, value).await
+    }
+
+    /// The F31 exchange alone, without validation, redundancy check or
+    /// write budget. Rollbacks use it so that putting back a value the
+    /// device held a moment ago cannot be refused.
+    pub(crate) async fn write_coefficent_unchecked(
+        &mut self,
+        coefficient: Coefficients,
+        value: f32,
     ) -> XLineResult<(), T::Error> {
         let be = value.to_be_bytes();
         let payload = [coefficient as u8, be[0], be[1], be[2], be[3]];
@@ -127,8 +487,20 @@ impl<T: XLineIO> KellerXLine<T> {
             function_code: base::FunctionCodes::WriteCoefficients,
             data: payload.into(),
         };
-        self.transaction(req, base::FunctionCodes::WriteCoefficients.response_len())
-            .await?;
+        let result = self
+            .transaction(req, base::FunctionCodes::WriteCoefficients.response_len())
+            .await;
+        self.wear.record_write(self.address, &result);
+        result?;
+        if self.verify.is_enabled() {
+            let actual = self.read_coefficent(coefficient).await?;
+            if !self.verify.matches(value, actual) {
+                return Err(ProtocolError::VerifyMismatch {
+                    expected: WrittenValue::Coefficient(coefficient, value),
+                    actual: WrittenValue::Coefficient(coefficient, actual),
+                });
+            }
+        }
         Ok(())
     }
 
@@ -136,6 +508,7 @@ impl<T: XLineIO> KellerXLine<T> {
         &mut self,
         variable: ConfigurationCommands,
     ) -> XLineResult<u8, T::Error> {
+        self.check_supported(UnsupportedEntry::Configuration(variable))?;
         let req = XLineFrame {
             address: self.address,
             function_code: base::FunctionCodes::ReadConfigurations,
@@ -147,40 +520,79 @@ impl<T: XLineIO> KellerXLine<T> {
         Ok(response.data_as_u8())
     }
 
+    /// Writes `value` after checking the entry is writable and `value` is
+    /// in range; see [`Self::force_write_configuration`] to skip the check.
     pub async fn write_configuration(
         &mut self,
         variable: ConfigurationCommands,
         value: u8,
     ) -> XLineResult<(), T::Error> {
+        variable
+            .info()
+            .validate(value)
+            .map_err(ProtocolError::Validation)?;
+        self.force_write_configuration(variable, value).await
+    }
+
+    /// Writes `value` as is, even to a read-only entry.
+    pub async fn force_write_configuration(
+        &mut self,
+        variable: ConfigurationCommands,
+        value: u8,
+    ) -> XLineResult<(), T::Error> {
+        self.check_supported(UnsupportedEntry::Configuration(variable))?;
+        if self.wear.skip_redundant && self.read_configuration(variable).await? == value {
+            self.wear.record_skip(self.address);
+            return Ok(());
+        }
+        self.check_write_budget()?;
         let req = XLineFrame {
             address: self.address,
             function_code: base::FunctionCodes::WriteConfiguration,
             data: [variable as u8, value].into(),
         };
-        self.transaction(req, base::FunctionCodes::WriteConfiguration.response_len())
-            .await?;
+        let result = self
+            .transaction(req, base::FunctionCodes::WriteConfiguration.response_len())
+            .await;
+        self.wear.record_write(self.address, &result);
+        result?;
+        if self.verify.is_enabled() {
+            let actual = self.read_configuration(variable).await?;
+            if actual != value {
+                return Err(ProtocolError::VerifyMismatch {
+                    expected: WrittenValue::Configuration(variable, value),
+                    actual: WrittenValue::Configuration(variable, actual),
+                });
+            }
+        }
         Ok(())
     }
 
-    pub async fn init_and_release(&mut self) -> XLineResult<(), T::Error> {
-        let req = XLineFrame {
-            address: self.address,
-            function_code: base::FunctionCodes::InitializeAndRealese,
-            data: [].into(),
-        };
-        self.transaction(
-            req,
-            base::FunctionCodes::InitializeAndRealese.response_len(),
-        )
-        .await?;
-        Ok(())
+    pub async fn init_and_release(&mut self) -> XLineResult<Identity, T::Error> {
+        self.initialize().await
     }
 
+    /// Moves the device to `address` (0 only reads it back) and returns the
+    /// address it now answers on.
+    ///
+    /// F66 goes to the device's current address and carries the new one as
+    /// its payload. The handle follows the device, so subsequent requests go
+    /// to the address it reported; a handle on the transparent address 250
+    /// keeps using 250, which still reaches the device.
+    ///
+    /// Addresses outside 1..=249 other than 0 fail with
+    /// [`ProtocolError::Validation`] without being sent.
     pub async fn write_address(&mut self, address: u8) -> XLineResult<u8, T::Error> {
+        if address != 0 {
+            ConfigurationCommands::DeviceAddress
+                .info()
+                .validate(address)
+                .map_err(ProtocolError::Validation)?;
+        }
         let req = XLineFrame {
-            address,
+            address: self.address,
             function_code: base::FunctionCodes::WriteAndReadNewDeviceAddress,
-            data: [].into(),
+            data: [address].into(),
         };
         let response = self
             .transaction(
@@ -188,10 +600,15 @@ impl<T: XLineIO> KellerXLine<T> {
                 base::FunctionCodes::WriteAndReadNewDeviceAddress.response_len(),
             )
             .await?;
-        Ok(response.data_as_u8())
+        let new_address = response.data_as_u8();
+        if self.address != TRANSPARENT_ADDRESS {
+            self.address = new_address;
+            self.state.address = new_address;
+        }
+        Ok(new_address)
     }
 
-    pub async fn read_serial_number(&mut self) -> XLineResult<u32, T::Error> {
+    pub async fn read_serial_number(&mut self) -> XLineResult<SerialNumber, T::Error> {
         let req = XLineFrame {
             address: self.address,
             function_code: base::FunctionCodes::ReadSerialNumber,
@@ -200,12 +617,29 @@ impl<T: XLineIO> KellerXLine<T> {
         let response = self
             .transaction(req, base::FunctionCodes::ReadSerialNumber.response_len())
             .await?;
-        Ok(response.payload[0] as u32 * 256
-            ^ 3 + response.payload[1] as u32 * 256
-            ^ 2 + response.payload[2] as u32 * 256 + response.payload[3] as u32)
+        SerialNumber::from_payload(&response.payload)
+            .ok_or(ProtocolError::FrameError(XLineFrameError::TooShort))
+    }
+
+    /// Reads the serial number and pairs it with the identity from the
+    /// most recent F48.
+    pub async fn device_info(&mut self) -> XLineResult<DeviceInfo, T::Error> {
+        Ok(DeviceInfo {
+            serial_number: self.read_serial_number().await?,
+            identity: self.identity(),
+        })
     }
 
     pub async fn read_channel_value(&mut self, channel: Channels) -> XLineResult<f32, T::Error> {
+        Ok(self.read_channel_value_and_status(channel).await?.0)
+    }
+
+    /// Like [`Self::read_channel_value`], also returning the status byte.
+    pub async fn read_channel_value_and_status(
+        &mut self,
+        channel: Channels,
+    ) -> XLineResult<(f32, ChannelStatus), T::Error> {
+        self.check_supported(UnsupportedEntry::Channel(channel))?;
         let req = XLineFrame {
             address: self.address,
             function_code: base::FunctionCodes::ReadChannelValueFloat,
@@ -217,10 +651,29 @@ impl<T: XLineIO> KellerXLine<T> {
                 base::FunctionCodes::ReadChannelValueFloat.response_len(),
             )
             .await?;
-        Ok(response.data_as_f32())
+        let status = ChannelStatus::from_bits_retain(response.payload[4]);
+        Ok((response.data_as_f32(), status))
+    }
+
+    pub async fn read_configuration_block(&mut self, index: u8) -> XLineResult<[u8; 5], T::Error> {
+        let req = XLineFrame {
+            address: self.address,
+            function_code: base::FunctionCodes::ReadConfigurationBlock,
+            data: [index].into(),
+        };
+        let response = self
+            .transaction(
+                req,
+                base::FunctionCodes::ReadConfigurationBlock.response_len(),
+            )
+            .await?;
+        let mut block = [0u8; 5];
+        block.copy_from_slice(&response.payload[..5]);
+        Ok(block)
     }
 
     pub async fn zero(&mut self, channel: ZeroCommands) -> XLineResult<(), T::Error> {
+        self.check_supported(UnsupportedEntry::Zero(channel))?;
         let req = XLineFrame {
             address: self.address,
             function_code: base::FunctionCodes::ZeroCommand,
@@ -236,6 +689,7 @@ impl<T: XLineIO> KellerXLine<T> {
         channel: ZeroCommands,
         value: f32,
     ) -> XLineResult<(), T::Error> {
+        self.check_supported(UnsupportedEntry::Zero(channel))?;
         let be = value.to_be_bytes();
         let payload = [channel as u8, be[0], be[1], be[2], be[3]];
         let req = XLineFrame {
@@ -248,3 +702,155 @@ impl<T: XLineIO> KellerXLine<T> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::base::{
+        Channels, ConfigurationCommands, KellerErrors, ProtocolError, XLineFrameError, ZeroCommands,
+    };
+    use crate::catalog::UnsupportedEntry;
+    use crate::emulator::{EmulatorError, XLineDevice};
+    use crate::testing::{Bus, TIMEOUT, block_on, client, identity};
+    use crate::{FirmwareVariant, KellerXLine, TRANSPARENT_ADDRESS, XLineEvent, XLineIO};
+
+    /// A device that loses its initialisation before every request but F48.
+    struct Forgetful<'a>(&'a Bus);
+
+    impl XLineIO for Forgetful<'_> {
+        type Error = EmulatorError;
+
+        async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Self::Error> {
+            if buf.get(1) != Some(&48) {
+                self.0.device.borrow_mut().power_cycle();
+            }
+            let mut bus = self.0;
+            bus.write_all(buf, timeout).await
+        }
+
+        async fn read_exact(
+            &mut self,
+            buf: &mut [u8],
+            timeout: Duration,
+        ) -> Result<(), Self::Error> {
+            let mut bus = self.0;
+            bus.read_exact(buf, timeout).await
+        }
+    }
+
+    #[test]
+    fn exceptions_to_long_replies_are_not_timeouts() {
+        let mut xline = client(24);
+        xline.set_auto_reinit(false);
+        xline.transport_mut().power_cycle();
+        // F73 expects nine bytes; the exception is five.
+        assert!(matches!(
+            block_on(xline.read_channel_value(Channels::P1)),
+            Err(ProtocolError::FrameError(XLineFrameError::DeviceError(
+                KellerErrors::DeviceNotInitialized
+            )))
+        ));
+        block_on(xline.init_and_release()).unwrap();
+        block_on(xline.read_channel_value(Channels::P1)).unwrap();
+    }
+
+    #[test]
+    fn restarted_device_is_reinitialised_and_asked_again() {
+        let bus = Bus::new(24);
+        let mut xline = bus.client();
+        bus.device.borrow_mut().power_cycle();
+        block_on(xline.read_channel_value(Channels::P1)).unwrap();
+        // The refused request, F48, then the retry.
+        assert_eq!((bus.requests.get(), bus.inits.get()), (3, 1));
+        assert_eq!(xline.restarts(), 1);
+        match xline.take_event() {
+            Some(XLineEvent::DeviceRestarted { address, identity }) => {
+                assert_eq!(address, 1);
+                assert!(identity.was_restarted());
+                assert_eq!(xline.identity(), identity);
+            }
+            None => panic!("no restart event"),
+        }
+        assert_eq!(xline.take_event(), None);
+    }
+
+    #[test]
+    fn reinit_is_retried_only_once() {
+        let bus = Bus::new(24);
+        let xline = KellerXLine::new(Forgetful(&bus), TIMEOUT, 1).unwrap();
+        let mut xline = block_on(xline.init()).map_err(|e| e.error).unwrap();
+        bus.requests.set(0);
+        bus.inits.set(0);
+        assert!(matches!(
+            block_on(xline.read_channel_value(Channels::P1)),
+            Err(ProtocolError::FrameError(XLineFrameError::DeviceError(
+                KellerErrors::DeviceNotInitialized
+            )))
+        ));
+        assert_eq!((bus.requests.get(), bus.inits.get()), (3, 1));
+        assert_eq!(xline.restarts(), 1);
+    }
+
+    #[test]
+    fn write_address_follows_the_device() {
+        let mut xline = client(24);
+        assert_eq!(block_on(xline.write_address(12)).unwrap(), 12);
+        assert_eq!(xline.address(), 12);
+        block_on(xline.read_serial_number()).unwrap();
+    }
+
+    #[test]
+    fn write_address_on_the_transparent_address_stays_there() {
+        let device = XLineDevice::new(1, identity(24), 1);
+        let xline = KellerXLine::new(device, TIMEOUT, TRANSPARENT_ADDRESS).unwrap();
+        let mut xline = block_on(xline.init()).map_err(|e| e.error).unwrap();
+        assert_eq!(block_on(xline.write_address(12)).unwrap(), 12);
+        assert_eq!(xline.address(), TRANSPARENT_ADDRESS);
+        assert_eq!(
+            block_on(xline.read_configuration(ConfigurationCommands::DeviceAddress)).unwrap(),
+            12
+        );
+    }
+
+    #[test]
+    fn resume_restores_address_and_firmware() {
+        let xline = client(24);
+        let device = xline.state();
+        let mut bus = xline.into_uninitialized();
+        bus.set_address(7);
+        assert_eq!(bus.firmware(), None);
+        let mut xline = bus.resume(device);
+        assert_eq!(xline.address(), 1);
+        assert_eq!(xline.identity(), device.identity());
+        assert_eq!(xline.firmware(), FirmwareVariant::from_identity(&identity(24)));
+        block_on(xline.read_serial_number()).unwrap();
+    }
+
+    #[test]
+    fn switch_to_keeps_address_and_identity_together() {
+        let mut xline = client(24);
+        let before = xline.state();
+        block_on(xline.write_address(12)).unwrap();
+        let after = xline.state();
+        assert_eq!(after.address(), 12);
+        assert_eq!(after.identity(), before.identity());
+        xline.switch_to(before);
+        assert_eq!(xline.address(), 1);
+        xline.switch_to(after);
+        block_on(xline.read_serial_number()).unwrap();
+    }
+
+    #[test]
+    fn zero_commands_are_checked_against_the_catalog() {
+        let mut xline = client(20);
+        assert!(matches!(
+            block_on(xline.zero(ZeroCommands::SetZeroT)),
+            Err(ProtocolError::Unsupported(UnsupportedEntry::Zero(
+                ZeroCommands::SetZeroT
+            )))
+        ));
+        block_on(xline.zero(ZeroCommands::SetZeroP1)).unwrap();
+        block_on(client(24).zero(ZeroCommands::SetZeroT)).unwrap();
+    }
+}
@@ -0,0 +1,420 @@
+use std::time::SystemTime;
+
+use crate::KellerXLine;
+use crate::base::{Channels, Identity, ProtocolError, SerialNumber, XLineIO};
+use crate::stability::StabilityError;
+
+/// A pressure controller (or calibrator) that can drive the reference
+/// pressure applied to the sensor.
+#[allow(async_fn_in_trait)]
+pub trait PressureController {
+    type Error;
+
+    /// Drives the output towards `setpoint`, returning once the controller
+    /// reports it has settled there.
+    async fn set_pressure(&mut self, setpoint: f32) -> Result<(), Self::Error>;
+
+    /// Pressure currently applied, as measured by the controller's own
+    /// reference.
+    async fn read_pressure(&mut self) -> Result<f32, Self::Error>;
+}
+
+/// Which way the pressure was moving when a step was taken.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SweepDirection {
+    Rising,
+    Falling,
+}
+
+/// Parameters of a linearity/hysteresis run.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearityTest {
+    pub channel: Channels,
+    /// Reference pressures; visited in ascending order, then descending.
+    /// The top setpoint is read once per cycle, not again at the start of
+    /// the falling sweep.
+    pub setpoints: Vec<f32>,
+    /// Rising/falling sweeps to run; two or more give a repeatability figure.
+    pub cycles: u8,
+    /// Readings averaged at each step once they are stable.
+    pub window: usize,
+    /// Largest standard deviation over `window` that counts as stable.
+    pub max_std_dev: f32,
+    /// Readings to take at one step before giving up on stability.
+    pub max_samples: usize,
+}
+
+/// One setpoint visit.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Step {
+    pub cycle: u8,
+    pub direction: SweepDirection,
+    pub setpoint: f32,
+    /// Pressure reported by the controller.
+    pub reference: f32,
+    /// Mean of the stable window read from the sensor.
+    pub measured: f32,
+    pub std_dev: f32,
+}
+
+impl Step {
+    pub fn error(&self) -> f32 {
+        self.measured - self.reference
+    }
+}
+
+/// Results of a run. Figures are in channel units; see
+/// [`LinearityReport::percent_of_span`] for %FS.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearityReport {
+    /// Device the run was taken on: the identity from its last F48 and the
+    /// serial number read (F67) at the start.
+    pub identity: Identity,
+    pub serial_number: SerialNumber,
+    /// When the run started.
+    pub started: SystemTime,
+    pub channel: Channels,
+    pub steps: Vec<Step>,
+    /// Highest minus lowest reference pressure.
+    pub span: f32,
+    /// Best-fit straight line (least squares) of measured against reference.
+    pub bfsl_slope: f32,
+    pub bfsl_intercept: f32,
+    /// Largest deviation of a reading from the best-fit straight line.
+    pub non_linearity: f32,
+    /// Largest rising/falling difference at one setpoint within a cycle.
+    pub hysteresis: f32,
+    /// Largest spread at one setpoint and direction across cycles; `None`
+    /// with a single cycle.
+    pub repeatability: Option<f32>,
+    /// Largest absolute difference between reading and reference.
+    pub max_error: f32,
+}
+
+impl LinearityReport {
+    pub fn percent_of_span(&self, value: f32) -> f32 {
+        value / self.span * 100.0
+    }
+
+    fn from_steps(
+        (identity, serial_number, started): (Identity, SerialNumber, SystemTime),
+        channel: Channels,
+        steps: Vec<Step>,
+        cycles: u8,
+    ) -> Self {
+        let n = steps.len() as f32;
+        let (min, max) = steps
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), s| {
+                (lo.min(s.reference), hi.max(s.reference))
+            });
+        let mean_x = steps.iter().map(|s| s.reference).sum::<f32>() / n;
+        let mean_y = steps.iter().map(|s| s.measured).sum::<f32>() / n;
+        let sxx: f32 = steps.iter().map(|s| (s.reference - mean_x).powi(2)).sum();
+        let sxy: f32 = steps
+            .iter()
+            .map(|s| (s.reference - mean_x) * (s.measured - mean_y))
+            .sum();
+        let bfsl_slope = sxy / sxx;
+        let bfsl_intercept = mean_y - bfsl_slope * mean_x;
+        let non_linearity = steps
+            .iter()
+            .map(|s| (s.measured - (bfsl_slope * s.reference + bfsl_intercept)).abs())
+            .fold(0.0, f32::max);
+
+        let matching = |a: &Step, b: &Step| a.setpoint == b.setpoint;
+        let hysteresis = steps
+            .iter()
+            .filter(|s| s.direction == SweepDirection::Rising)
+            .flat_map(|r| {
+                steps
+                    .iter()
+                    .filter(move |f| {
+                        f.direction == SweepDirection::Falling
+                            && f.cycle == r.cycle
+                            && matching(r, f)
+                    })
+                    .map(move |f| (r.error() - f.error()).abs())
+            })
+            .fold(0.0, f32::max);
+
+        let repeatability = (cycles > 1).then(|| {
+            steps
+                .iter()
+                .map(|a| {
+                    steps
+                        .iter()
+                        .filter(|b| b.direction == a.direction && matching(a, b))
+                        .map(|b| (a.error() - b.error()).abs())
+                        .fold(0.0, f32::max)
+                })
+                .fold(0.0, f32::max)
+        });
+        let max_error = steps.iter().map(|s| s.error().abs()).fold(0.0, f32::max);
+
+        LinearityReport {
+            identity,
+            serial_number,
+            started,
+            channel,
+            steps,
+            span: max - min,
+            bfsl_slope,
+            bfsl_intercept,
+            non_linearity,
+            hysteresis,
+            repeatability,
+            max_error,
+        }
+    }
+}
+
+/// Errors raised by [`KellerXLine::run_linearity_test`].
+#[derive(Debug, thiserror::Error)]
+pub enum LinearityError<TE, CE> {
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError<TE>),
+    #[error("pressure controller: {0:?}")]
+    Controller(CE),
+    #[error("reading at {setpoint} did not settle (std dev {std_dev})")]
+    NotStable { setpoint: f32, std_dev: f32 },
+    #[error("at least two distinct setpoints and one cycle are needed")]
+    TooFewSetpoints,
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    /// Steps the controller through `test.setpoints` rising then falling,
+    /// `test.cycles` times, and records a stable averaged reading of
+    /// `test.channel` at each step.
+    ///
+    /// The reference is read from the controller after the sensor has
+    /// settled, so it reflects the pressure the reading was taken at.
+    pub async fn run_linearity_test<C: PressureController>(
+        &mut self,
+        test: &LinearityTest,
+        controller: &mut C,
+    ) -> Result<LinearityReport, LinearityError<T::Error, C::Error>> {
+        let mut rising = test.setpoints.clone();
+        rising.sort_by(f32::total_cmp);
+        rising.dedup();
+        if rising.len() < 2 || test.cycles == 0 {
+            return Err(LinearityError::TooFewSetpoints);
+        }
+        // The rising sweep ends on the top setpoint; reading it again at
+        // once would only weight it twice in the fit.
+        let falling: Vec<f32> = rising.iter().rev().skip(1).copied().collect();
+
+        let started = SystemTime::now();
+        let identity = self.identity();
+        let serial_number = self.read_serial_number().await?;
+        let mut steps = Vec::with_capacity((rising.len() + falling.len()) * test.cycles as usize);
+        for cycle in 0..test.cycles {
+            for (direction, setpoints) in [
+                (SweepDirection::Rising, &rising),
+                (SweepDirection::Falling, &falling),
+            ] {
+                for &setpoint in setpoints {
+                    controller
+                        .set_pressure(setpoint)
+                        .await
+                        .map_err(LinearityError::Controller)?;
+                    let reading = self
+                        .read_channel_stable(
+                            test.channel,
+                            test.window,
+                            test.max_std_dev,
+                            test.max_samples,
+                        )
+                        .await
+                        .map_err(|e| match e {
+                            StabilityError::Protocol(e) => LinearityError::Protocol(e),
+                            StabilityError::NotStable { std_dev } => {
+                                LinearityError::NotStable { setpoint, std_dev }
+                            }
+                        })?;
+                    let reference = controller
+                        .read_pressure()
+                        .await
+                        .map_err(LinearityError::Controller)?;
+                    steps.push(Step {
+                        cycle,
+                        direction,
+                        setpoint,
+                        reference,
+                        measured: reading.mean,
+                        std_dev: reading.std_dev,
+                    });
+                }
+            }
+        }
+        Ok(LinearityReport::from_steps(
+            (identity, serial_number, started),
+            test.channel,
+            steps,
+            test.cycles,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::{LinearityTest, PressureController, SweepDirection};
+    use crate::base::{Channels, SerialNumber};
+    use crate::testing::{block_on, client, pressurised};
+
+    /// Reports whatever it was last asked for.
+    #[derive(Default)]
+    struct Ideal(f32);
+
+    impl PressureController for Ideal {
+        type Error = ();
+
+        async fn set_pressure(&mut self, setpoint: f32) -> Result<(), ()> {
+            self.0 = setpoint;
+            Ok(())
+        }
+
+        async fn read_pressure(&mut self) -> Result<f32, ()> {
+            Ok(self.0)
+        }
+    }
+
+    /// Applies a scripted sensor pressure at each step and reports a
+    /// scripted reference.
+    struct Scripted<'a> {
+        p1: &'a Cell<f32>,
+        /// `(sensor, reference)` per step.
+        steps: &'a [(f32, f32)],
+        next: usize,
+    }
+
+    impl PressureController for Scripted<'_> {
+        type Error = ();
+
+        async fn set_pressure(&mut self, _setpoint: f32) -> Result<(), ()> {
+            self.p1.set(self.steps[self.next].0);
+            Ok(())
+        }
+
+        async fn read_pressure(&mut self) -> Result<f32, ()> {
+            self.next += 1;
+            Ok(self.steps[self.next - 1].1)
+        }
+    }
+
+    fn test(cycles: u8) -> LinearityTest {
+        LinearityTest {
+            channel: Channels::P1,
+            setpoints: vec![0.0, 1.0, 2.0],
+            cycles,
+            window: 3,
+            max_std_dev: 0.01,
+            max_samples: 10,
+        }
+    }
+
+    #[test]
+    fn non_linearity_is_the_largest_distance_from_the_bfsl() {
+        // Reads 0.5 high at mid-scale on both sweeps. Against reference x
+        // the readings y are (0, 0), (1, 1.5), (2, 2), (1, 1.5), (0, 0):
+        // slope = (7 - 5 * 0.8 * 1.0) / (6 - 5 * 0.8²) = 15/14, intercept
+        // 1/7, so the mid-scale points sit 1.5 - 17/14 = 2/7 above the line
+        // and the top one 32/14 - 2 = 2/7 below it.
+        let p1 = Cell::new(0.0);
+        let mut xline = pressurised(24, &p1);
+        let steps = [(0.0, 0.0), (1.5, 1.0), (2.0, 2.0), (1.5, 1.0), (0.0, 0.0)];
+        let mut controller = Scripted {
+            p1: &p1,
+            steps: &steps,
+            next: 0,
+        };
+        let report = block_on(xline.run_linearity_test(&test(1), &mut controller)).unwrap();
+        assert_eq!(report.span, 2.0);
+        assert!((report.bfsl_slope - 15.0 / 14.0).abs() < 1e-6);
+        assert!((report.bfsl_intercept - 1.0 / 7.0).abs() < 1e-6);
+        assert!((report.non_linearity - 2.0 / 7.0).abs() < 1e-6);
+        assert_eq!(report.hysteresis, 0.0);
+        assert_eq!(report.repeatability, None);
+        assert_eq!(report.max_error, 0.5);
+        assert_eq!(report.percent_of_span(report.max_error), 25.0);
+    }
+
+    #[test]
+    fn hysteresis_and_repeatability_compare_errors_at_one_setpoint() {
+        // The sensor reads the setpoint; the reference at 1.0 is 0.25 and
+        // 0.5 below it rising and 0.75 below it falling.
+        let p1 = Cell::new(0.0);
+        let mut xline = pressurised(24, &p1);
+        let steps = [
+            (0.0, 0.0),
+            (1.0, 0.75),
+            (2.0, 2.0),
+            (1.0, 0.25),
+            (0.0, 0.0),
+            (0.0, 0.0),
+            (1.0, 0.5),
+            (2.0, 2.0),
+            (1.0, 0.25),
+            (0.0, 0.0),
+        ];
+        let mut controller = Scripted {
+            p1: &p1,
+            steps: &steps,
+            next: 0,
+        };
+        let report = block_on(xline.run_linearity_test(&test(2), &mut controller)).unwrap();
+        let errors: Vec<_> = report.steps.iter().map(|s| s.error()).collect();
+        assert_eq!(
+            errors,
+            [0.0, 0.25, 0.0, 0.75, 0.0, 0.0, 0.5, 0.0, 0.75, 0.0]
+        );
+        // |0.25 - 0.75| in the first cycle, |0.5 - 0.75| in the second.
+        assert_eq!(report.hysteresis, 0.5);
+        // Rising at 1.0: |0.25 - 0.5|; falling errors match.
+        assert_eq!(report.repeatability, Some(0.25));
+        assert_eq!(report.max_error, 0.75);
+    }
+
+    #[test]
+    fn top_setpoint_is_read_once_per_cycle() {
+        let mut xline = client(24);
+        let test = LinearityTest {
+            channel: Channels::P1,
+            setpoints: vec![0.0, 2.0, 1.0],
+            cycles: 2,
+            window: 3,
+            max_std_dev: 0.01,
+            max_samples: 10,
+        };
+        let report = block_on(xline.run_linearity_test(&test, &mut Ideal::default())).unwrap();
+        let visits: Vec<_> = report
+            .steps
+            .iter()
+            .map(|s| (s.cycle, s.direction, s.setpoint))
+            .collect();
+        use SweepDirection::{Falling, Rising};
+        assert_eq!(
+            visits,
+            [
+                (0, Rising, 0.0),
+                (0, Rising, 1.0),
+                (0, Rising, 2.0),
+                (0, Falling, 1.0),
+                (0, Falling, 0.0),
+                (1, Rising, 0.0),
+                (1, Rising, 1.0),
+                (1, Rising, 2.0),
+                (1, Falling, 1.0),
+                (1, Falling, 0.0),
+            ]
+        );
+        assert_eq!(report.serial_number, SerialNumber(4_000_123));
+        assert_eq!(report.identity, xline.identity());
+    }
+}
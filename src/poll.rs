@@ -0,0 +1,89 @@
+use crate::base::{ChannelStatus, Channels, ProtocolError, XLineFrameError, XLineIO};
+use crate::{KellerXLine, XLineResult};
+
+/// Running counters of request outcomes on one link or device.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub requests: u32,
+    pub ok: u32,
+    /// Transport failures, including timeouts.
+    pub transport_errors: u32,
+    pub crc_errors: u32,
+    /// Exception replies from the device.
+    pub device_errors: u32,
+    /// Replies that were well-formed but did not answer the request.
+    pub protocol_errors: u32,
+}
+
+impl LinkStats {
+    /// Counts one request; those rejected before reaching the wire
+    /// ([`ProtocolError::Unsupported`], [`ProtocolError::Validation`],
+    /// [`ProtocolError::WriteBudgetExceeded`]) are ignored.
+    pub fn record<T, E>(&mut self, result: &XLineResult<T, E>) {
+        if let Err(
+            ProtocolError::Unsupported(_)
+            | ProtocolError::Validation(_)
+            | ProtocolError::WriteBudgetExceeded { .. },
+        ) = result
+        {
+            return;
+        }
+        self.requests += 1;
+        match result {
+            Ok(_) => self.ok += 1,
+            Err(ProtocolError::Transport(_) | ProtocolError::Timeout) => self.transport_errors += 1,
+            Err(ProtocolError::FrameError(XLineFrameError::BadCrc { .. })) => self.crc_errors += 1,
+            Err(ProtocolError::FrameError(XLineFrameError::DeviceError(_))) => {
+                self.device_errors += 1
+            }
+            Err(_) => self.protocol_errors += 1,
+        }
+    }
+
+    /// Adds another set of counters to this one.
+    pub fn merge(&mut self, other: &LinkStats) {
+        self.requests += other.requests;
+        self.ok += other.ok;
+        self.transport_errors += other.transport_errors;
+        self.crc_errors += other.crc_errors;
+        self.device_errors += other.device_errors;
+        self.protocol_errors += other.protocol_errors;
+    }
+
+    /// Fraction of requests that failed, 0.0 when nothing was sent yet.
+    pub fn error_rate(&self) -> f32 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        (self.requests - self.ok) as f32 / self.requests as f32
+    }
+}
+
+/// Reads `channels` from the device in order, storing each value (or
+/// `None` on failure) in the matching slot of `values` and counting every
+/// request in `stats`. Returns the status byte of the last successful read.
+///
+/// One poll round of a bus monitor: a failing channel never stops the
+/// remaining ones from being read.
+pub async fn poll_channels<T: XLineIO>(
+    xline: &mut KellerXLine<T>,
+    channels: &[Channels],
+    values: &mut [Option<f32>],
+    stats: &mut LinkStats,
+) -> Option<ChannelStatus> {
+    let mut status = None;
+    for (channel, slot) in channels.iter().zip(values.iter_mut()) {
+        let result = xline.read_channel_value_and_status(*channel).await;
+        stats.record(&result);
+        *slot = match result {
+            Ok((value, s)) => {
+                status = Some(s);
+                Some(value)
+            }
+            Err(_) => None,
+        };
+    }
+    status
+}
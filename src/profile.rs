@@ -0,0 +1,519 @@
+use std::collections::BTreeMap;
+
+use crate::base::{Coefficients, ConfigurationCommands, Identity, ProtocolError, XLineIO};
+use crate::registry::Access;
+use crate::snapshot::{DeviceSnapshot, supported};
+use crate::{KellerXLine, XLineResult};
+
+/// Desired coefficients and configuration for a family of sensors. Entries
+/// that are not listed are left alone.
+///
+/// With the `serde` feature a profile loads from any serde format, and
+/// unknown keys are rejected so a typo does not pass silently; in TOML it
+/// reads as
+///
+/// ```toml
+/// [coefficients]
+/// GainFactorP1 = 1.0
+///
+/// [configuration]
+/// Filter = 3
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceProfile {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub coefficients: BTreeMap<Coefficients, f32>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub configuration: BTreeMap<ConfigurationCommands, u8>,
+}
+
+impl From<&DeviceSnapshot> for DeviceProfile {
+    fn from(snapshot: &DeviceSnapshot) -> Self {
+        Self {
+            coefficients: snapshot.coefficients.iter().copied().collect(),
+            configuration: snapshot.configuration.iter().copied().collect(),
+        }
+    }
+}
+
+/// One write needed to bring a device in line with a profile.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Change {
+    Configuration {
+        entry: ConfigurationCommands,
+        from: u8,
+        to: u8,
+    },
+    Coefficient {
+        coefficient: Coefficients,
+        from: f32,
+        to: f32,
+    },
+}
+
+impl core::fmt::Display for Change {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Change::Configuration { entry, from, to } => {
+                write!(f, "config {:>3} {entry:?}: {from} -> {to}", *entry as u8)
+            }
+            Change::Coefficient {
+                coefficient,
+                from,
+                to,
+            } => write!(
+                f,
+                "coef   {:>3} {coefficient:?}: {from} -> {to}",
+                *coefficient as u8
+            ),
+        }
+    }
+}
+
+/// Ordered list of writes produced by [`KellerXLine::plan`] or
+/// [`DeviceSnapshot::diff`].
+///
+/// Configuration comes before coefficients because registers such as the
+/// pressure unit decide how coefficient values are read; within each group
+/// entries are in ID order.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    fn sort(&mut self) {
+        self.changes.sort_by_key(|change| match change {
+            Change::Configuration { entry, .. } => (0, *entry as u8),
+            Change::Coefficient { coefficient, .. } => (1, *coefficient as u8),
+        });
+    }
+}
+
+/// What [`KellerXLine::apply`] did, or would have done on a dry run.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApplyReport {
+    pub dry_run: bool,
+    pub written: Vec<Change>,
+    /// Entries that already held their target value.
+    pub unchanged: usize,
+    /// Entries the device does not implement, which
+    /// [`KellerXLine::restore`] leaves out.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub unsupported: usize,
+}
+
+/// Errors raised by [`KellerXLine::apply`] and [`KellerXLine::restore`].
+#[derive(Debug, thiserror::Error)]
+pub enum ApplyError<E> {
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError<E>),
+    #[error("snapshot is from firmware {expected:?}, device is {actual:?}")]
+    IdentityMismatch {
+        expected: Identity,
+        actual: Identity,
+    },
+    #[error("coefficient {coefficient:?} reads back {actual} after writing {expected}")]
+    CoefficientMismatch {
+        coefficient: Coefficients,
+        expected: f32,
+        actual: f32,
+    },
+    #[error("configuration {entry:?} reads back {actual} after writing {expected}")]
+    ConfigurationMismatch {
+        entry: ConfigurationCommands,
+        expected: u8,
+        actual: u8,
+    },
+}
+
+/// What [`KellerXLine::plan_entries`] found.
+pub(crate) struct Planned {
+    pub plan: Plan,
+    /// Entries that already hold their target value.
+    pub unchanged: usize,
+    /// Entries the device does not implement.
+    pub unsupported: usize,
+}
+
+/// Floats compare bit for bit, matching what goes over the wire.
+fn same(a: f32, b: f32) -> bool {
+    a.to_bits() == b.to_bits()
+}
+
+impl DeviceSnapshot {
+    /// Changes that turn the device this snapshot was taken from into
+    /// `target`. Entries missing from either side are not compared.
+    pub fn diff(&self, target: &DeviceSnapshot) -> Plan {
+        let mut plan = Plan::default();
+        for &(entry, from) in &self.configuration {
+            match target.configuration(entry) {
+                Some(to) if to != from => {
+                    plan.changes.push(Change::Configuration { entry, from, to })
+                }
+                _ => {}
+            }
+        }
+        for &(coefficient, from) in &self.coefficients {
+            match target.coefficient(coefficient) {
+                Some(to) if !same(from, to) => plan.changes.push(Change::Coefficient {
+                    coefficient,
+                    from,
+                    to,
+                }),
+                _ => {}
+            }
+        }
+        plan.sort();
+        plan
+    }
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    /// Compares `profile` against the device and lists the writes needed.
+    ///
+    /// [`ConfigurationCommands::DeviceAddress`] is never planned, since a
+    /// profile is shared between devices on one bus; use
+    /// [`KellerXLine::write_address`] to move a device. Read-only entries
+    /// are skipped as well.
+    pub async fn plan(&mut self, profile: &DeviceProfile) -> XLineResult<Plan, T::Error> {
+        self.plan_entries(profile, false)
+            .await
+            .map(|planned| planned.plan)
+    }
+
+    /// [`KellerXLine::plan`], optionally leaving out entries the device
+    /// does not implement and counting them instead of failing.
+    pub(crate) async fn plan_entries(
+        &mut self,
+        profile: &DeviceProfile,
+        skip_unsupported: bool,
+    ) -> XLineResult<Planned, T::Error> {
+        let mut plan = Plan::default();
+        let mut unchanged = 0;
+        let mut unsupported = 0;
+        for (&entry, &to) in &profile.configuration {
+            if entry == ConfigurationCommands::DeviceAddress
+                || entry.info().access == Access::ReadOnly
+            {
+                continue;
+            }
+            let read = self.read_configuration(entry).await;
+            let read = if skip_unsupported {
+                supported(read)?
+            } else {
+                Some(read?)
+            };
+            let Some(from) = read else {
+                unsupported += 1;
+                continue;
+            };
+            if from != to {
+                plan.changes.push(Change::Configuration { entry, from, to });
+            } else {
+                unchanged += 1;
+            }
+        }
+        for (&coefficient, &to) in &profile.coefficients {
+            let read = self.read_coefficent(coefficient).await;
+            let read = if skip_unsupported {
+                supported(read)?
+            } else {
+                Some(read?)
+            };
+            let Some(from) = read else {
+                unsupported += 1;
+                continue;
+            };
+            if !same(from, to) {
+                plan.changes.push(Change::Coefficient {
+                    coefficient,
+                    from,
+                    to,
+                });
+            } else {
+                unchanged += 1;
+            }
+        }
+        plan.sort();
+        Ok(Planned {
+            plan,
+            unchanged,
+            unsupported,
+        })
+    }
+
+    /// Performs the writes in `plan` in order, reading each value back to
+    /// verify it. Entries that already hold their target value are skipped,
+    /// so applying a plan twice is harmless.
+    ///
+    /// With `dry_run` nothing is written; the report lists the writes that
+    /// would have been made against the device as it is now.
+    ///
+    /// Every target value is validated before the first write, so a plan
+    /// with one bad value fails without changing the device.
+    pub async fn apply(
+        &mut self,
+        plan: &Plan,
+        dry_run: bool,
+    ) -> Result<ApplyReport, ApplyError<T::Error>> {
+        for change in &plan.changes {
+            match *change {
+                Change::Configuration { entry, to, .. } => entry.info().validate(to),
+                Change::Coefficient {
+                    coefficient, to, ..
+                } => coefficient.info().validate(to),
+            }
+            .map_err(ProtocolError::Validation)?;
+        }
+        self.apply_changes(plan, dry_run, false).await
+    }
+
+    /// [`KellerXLine::apply`] without validation when `force` is set, for
+    /// values read from a device, such as an unset (NaN) coefficient.
+    pub(crate) async fn apply_changes(
+        &mut self,
+        plan: &Plan,
+        dry_run: bool,
+        force: bool,
+    ) -> Result<ApplyReport, ApplyError<T::Error>> {
+        let mut report = ApplyReport {
+            dry_run,
+            ..ApplyReport::default()
+        };
+        for &change in &plan.changes {
+            match change {
+                Change::Configuration { entry, to, .. } => {
+                    let from = self.read_configuration(entry).await?;
+                    if from == to {
+                        report.unchanged += 1;
+                        continue;
+                    }
+                    if !dry_run {
+                        if force {
+                            self.force_write_configuration(entry, to).await?;
+                        } else {
+                            self.write_configuration(entry, to).await?;
+                        }
+                        let actual = self.read_configuration(entry).await?;
+                        if actual != to {
+                            return Err(ApplyError::ConfigurationMismatch {
+                                entry,
+                                expected: to,
+                                actual,
+                            });
+                        }
+                    }
+                    report
+                        .written
+                        .push(Change::Configuration { entry, from, to });
+                }
+                Change::Coefficient {
+                    coefficient, to, ..
+                } => {
+                    let from = self.read_coefficent(coefficient).await?;
+                    if same(from, to) {
+                        report.unchanged += 1;
+                        continue;
+                    }
+                    if !dry_run {
+                        if force {
+                            self.force_write_coefficent(coefficient, to).await?;
+                        } else {
+                            self.write_coefficent(coefficient, to).await?;
+                        }
+                        let actual = self.read_coefficent(coefficient).await?;
+                        if !same(actual, to) {
+                            return Err(ApplyError::CoefficientMismatch {
+                                coefficient,
+                                expected: to,
+                                actual,
+                            });
+                        }
+                    }
+                    report.written.push(Change::Coefficient {
+                        coefficient,
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::{ApplyError, Change, DeviceProfile, Plan};
+    use crate::base::{Coefficients as C, ConfigurationCommands as K, ProtocolError};
+    use crate::testing::{block_on, client};
+
+    fn profile() -> DeviceProfile {
+        let mut profile = DeviceProfile::default();
+        profile.coefficients.insert(C::GainFactorP1, 2.0);
+        profile.coefficients.insert(C::PressureOffsetP1, 0.0);
+        profile.coefficients.insert(C::Free100, 0.5);
+        profile.configuration.insert(K::SPS, 4);
+        profile.configuration.insert(K::Filter, 3);
+        profile.configuration.insert(K::DeviceAddress, 9);
+        profile.configuration.insert(K::Status, 1);
+        profile
+    }
+
+    #[test]
+    fn plan_puts_configuration_first_in_id_order() {
+        let mut xline = client(24);
+        let plan = block_on(xline.plan(&profile())).unwrap();
+        assert_eq!(
+            plan.changes,
+            vec![
+                Change::Configuration {
+                    entry: K::Filter,
+                    from: 0,
+                    to: 3,
+                },
+                Change::Configuration {
+                    entry: K::SPS,
+                    from: 0,
+                    to: 4,
+                },
+                Change::Coefficient {
+                    coefficient: C::GainFactorP1,
+                    from: 1.0,
+                    to: 2.0,
+                },
+                Change::Coefficient {
+                    coefficient: C::Free100,
+                    from: 0.0,
+                    to: 0.5,
+                },
+            ]
+        );
+        let lines: Vec<_> = plan.changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "config   7 Filter: 0 -> 3",
+                "config  15 SPS: 0 -> 4",
+                "coef    65 GainFactorP1: 1 -> 2",
+                "coef   100 Free100: 0 -> 0.5",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_lists_only_entries_that_differ() {
+        let mut xline = client(24);
+        let before = block_on(xline.snapshot()).unwrap();
+        block_on(xline.write_configuration(K::Filter, 3)).unwrap();
+        block_on(xline.write_coefficent(C::GainFactorP1, 2.0)).unwrap();
+        let after = block_on(xline.snapshot()).unwrap();
+        assert_eq!(
+            before.diff(&after).changes,
+            vec![
+                Change::Configuration {
+                    entry: K::Filter,
+                    from: 0,
+                    to: 3,
+                },
+                Change::Coefficient {
+                    coefficient: C::GainFactorP1,
+                    from: 1.0,
+                    to: 2.0,
+                },
+            ]
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let mut xline = client(24);
+        let plan = block_on(xline.plan(&profile())).unwrap();
+        let report = block_on(xline.apply(&plan, true)).unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.written, plan.changes);
+        assert_eq!(xline.transport_mut().eeprom_writes(), 0);
+        assert_eq!(block_on(xline.plan(&profile())).unwrap(), plan);
+    }
+
+    #[test]
+    fn apply_writes_and_reads_back() {
+        let mut xline = client(24);
+        let plan = block_on(xline.plan(&profile())).unwrap();
+        let report = block_on(xline.apply(&plan, false)).unwrap();
+        assert_eq!(report.written, plan.changes);
+        assert_eq!(xline.transport_mut().eeprom_writes(), 4);
+        let device = xline.transport_mut();
+        assert_eq!(device.configuration(K::Filter), Some(3));
+        assert_eq!(device.configuration(K::DeviceAddress), Some(1));
+        assert_eq!(device.coefficient(C::Free100), Some(0.5));
+        assert!(block_on(xline.plan(&profile())).unwrap().is_empty());
+    }
+
+    #[test]
+    fn apply_validates_the_whole_plan_first() {
+        let mut xline = client(24);
+        let plan = Plan {
+            changes: vec![
+                Change::Configuration {
+                    entry: K::Filter,
+                    from: 0,
+                    to: 3,
+                },
+                Change::Coefficient {
+                    coefficient: C::GainFactorP1,
+                    from: 1.0,
+                    to: 0.0,
+                },
+            ],
+        };
+        assert!(matches!(
+            block_on(xline.apply(&plan, false)),
+            Err(ApplyError::Protocol(ProtocolError::Validation(_)))
+        ));
+        assert_eq!(xline.transport_mut().eeprom_writes(), 0);
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn profiles_load_from_toml() {
+        let profile: DeviceProfile =
+            toml::from_str("[coefficients]\nGainFactorP1 = 2.0\n\n[configuration]\nFilter = 3\n")
+                .unwrap();
+        assert_eq!(profile.coefficients[&C::GainFactorP1], 2.0);
+        assert_eq!(profile.configuration[&K::Filter], 3);
+        assert!(toml::from_str::<DeviceProfile>("[coefficent]\nGainFactorP1 = 2.0\n").is_err());
+        assert!(toml::from_str::<DeviceProfile>("[coefficients]\nGain = 2.0\n").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn profiles_load_from_json() {
+        let profile: DeviceProfile =
+            serde_json::from_str(r#"{"coefficients": {"Free100": 0.5}}"#).unwrap();
+        assert_eq!(profile.coefficients[&C::Free100], 0.5);
+        assert!(profile.configuration.is_empty());
+        assert!(
+            serde_json::from_str::<DeviceProfile>(r#"{"coefficients": {}, "filter": 3}"#).is_err()
+        );
+    }
+}
@@ -0,0 +1,745 @@
+//! Static metadata for every coefficient and configuration register.
+//!
+//! Names, units and value domains follow the protocol documentation.
+//! Several fields are placeholders, not published facts:
+//!
+//! - coefficient limits are `f32::MIN..=f32::MAX` except where a negative
+//!   value is meaningless (the square-root threshold and the conductivity
+//!   cell constant), since no ranges are documented;
+//! - every coefficient and writable register is marked [`Access::ReadWrite`]
+//!   with `eeprom: true`, as the documentation does not say which entries
+//!   are volatile;
+//! - configuration defaults are all `None`, as no factory values are
+//!   documented;
+//! - coefficient defaults are neutral values (1 for gains and the cell
+//!   constant, the usual 0.022/K for the conductivity temperature
+//!   coefficient, 0 otherwise), not documented factory values;
+//! - the layout of coefficients 140…156 is unconfirmed, so they are only
+//!   named by number (the `unstable-curve` feature assumes one).
+
+use crate::base::{Coefficients, ConfigurationCommands};
+
+/// Whether an entry can be written.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Access {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// Static description of one coefficient.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CoefficientInfo {
+    pub id: Coefficients,
+    /// The variant name, e.g. `GainFactorP1`.
+    pub key: &'static str,
+    pub name: &'static str,
+    /// Empty for dimensionless values.
+    pub unit: &'static str,
+    pub default: f32,
+    /// Inclusive limits of a meaningful value.
+    pub min: f32,
+    pub max: f32,
+    /// Zero is not allowed, e.g. for gain factors.
+    pub nonzero: bool,
+    pub access: Access,
+    /// Writing the value stores it in EEPROM (and wears it).
+    pub eeprom: bool,
+}
+
+/// Static description of one configuration register.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ConfigurationInfo {
+    pub id: ConfigurationCommands,
+    /// The variant name, e.g. `DeviceAddress`.
+    pub key: &'static str,
+    pub name: &'static str,
+    pub unit: &'static str,
+    /// Factory value; `None` where it differs per device or the protocol
+    /// documentation does not state it.
+    pub default: Option<u8>,
+    pub domain: Domain,
+    pub access: Access,
+    pub eeprom: bool,
+}
+
+/// Values a configuration register accepts.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Domain {
+    /// Any value in `min..=max`.
+    Range { min: u8, max: u8 },
+    /// One of the listed codes.
+    Codes(&'static [u8]),
+    /// Any combination of the bits set in the mask.
+    Bits(u8),
+}
+
+impl Domain {
+    pub fn contains(&self, value: u8) -> bool {
+        match *self {
+            Domain::Range { min, max } => (min..=max).contains(&value),
+            Domain::Codes(codes) => codes.contains(&value),
+            Domain::Bits(mask) => value & !mask == 0,
+        }
+    }
+}
+
+impl core::fmt::Display for Domain {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Domain::Range { min, max } => write!(f, "{min}..={max}"),
+            Domain::Codes(codes) => write!(f, "{codes:?}"),
+            Domain::Bits(mask) => write!(f, "mask {mask:#010b}"),
+        }
+    }
+}
+
+/// Registers whose codes are not published keep the whole byte.
+const ANY: Domain = Domain::Range { min: 0, max: 255 };
+const ON_OFF: Domain = Domain::Codes(&[0, 1]);
+/// One bit per channel, numbered as in F73: CH0, P1, P2, T, TOB1, TOB2.
+const CHANNEL_BITS: Domain = Domain::Bits(0b0011_1111);
+
+const fn coef(
+    id: Coefficients,
+    key: &'static str,
+    name: &'static str,
+    unit: &'static str,
+    default: f32,
+) -> CoefficientInfo {
+    CoefficientInfo {
+        id,
+        key,
+        name,
+        unit,
+        default,
+        min: f32::MIN,
+        max: f32::MAX,
+        nonzero: false,
+        access: Access::ReadWrite,
+        eeprom: true,
+    }
+}
+
+const fn coef_min(info: CoefficientInfo, min: f32) -> CoefficientInfo {
+    CoefficientInfo { min, ..info }
+}
+
+const fn nonzero(info: CoefficientInfo) -> CoefficientInfo {
+    CoefficientInfo {
+        nonzero: true,
+        ..info
+    }
+}
+
+const fn cfg(
+    id: ConfigurationCommands,
+    key: &'static str,
+    name: &'static str,
+    unit: &'static str,
+    domain: Domain,
+) -> ConfigurationInfo {
+    ConfigurationInfo {
+        id,
+        key,
+        name,
+        unit,
+        default: None,
+        domain,
+        access: Access::ReadWrite,
+        eeprom: true,
+    }
+}
+
+const fn read_only(info: ConfigurationInfo) -> ConfigurationInfo {
+    ConfigurationInfo {
+        access: Access::ReadOnly,
+        eeprom: false,
+        ..info
+    }
+}
+
+use Coefficients as C;
+use ConfigurationCommands as K;
+
+/// Every coefficient, in ID order.
+pub static COEFFICIENTS: [CoefficientInfo; 50] = [
+    coef_min(
+        coef(
+            C::ThresholdSquareRoot,
+            "ThresholdSquareRoot",
+            "Square-root threshold",
+            "bar",
+            0.0,
+        ),
+        0.0,
+    ),
+    coef(
+        C::PressureOffsetP1,
+        "PressureOffsetP1",
+        "P1 offset",
+        "bar",
+        0.0,
+    ),
+    nonzero(coef(C::GainFactorP1, "GainFactorP1", "P1 gain", "", 1.0)),
+    coef(
+        C::PressureOffsetP2,
+        "PressureOffsetP2",
+        "P2 offset",
+        "bar",
+        0.0,
+    ),
+    nonzero(coef(C::GainFactorP2, "GainFactorP2", "P2 gain", "", 1.0)),
+    coef(
+        C::OffsetAnalogOutput,
+        "OffsetAnalogOutput",
+        "Analogue output offset",
+        "bar",
+        0.0,
+    ),
+    nonzero(coef(
+        C::GainFactorAnalogOutput,
+        "GainFactorAnalogOutput",
+        "Analogue output gain",
+        "",
+        1.0,
+    )),
+    coef(C::OffsetCh0, "OffsetCh0", "CH0 offset", "", 0.0),
+    nonzero(coef(C::GainFactorCh0, "GainFactorCh0", "CH0 gain", "", 1.0)),
+    coef(
+        C::TemperatureOffsetTorUpperThresSw1,
+        "TemperatureOffsetTorUpperThresSw1",
+        "T offset (v5.20: switching output 1 upper threshold)",
+        "°C",
+        0.0,
+    ),
+    coef(
+        C::LowerThresSw1,
+        "LowerThresSw1",
+        "Switching output 1 lower threshold",
+        "bar",
+        0.0,
+    ),
+    coef(
+        C::TemperatureOffsetTOB1,
+        "TemperatureOffsetTOB1",
+        "TOB1 offset",
+        "°C",
+        0.0,
+    ),
+    coef(
+        C::TemperatureOffsetTOB2,
+        "TemperatureOffsetTOB2",
+        "TOB2 offset",
+        "°C",
+        0.0,
+    ),
+    coef(
+        C::UpperThresSw2,
+        "UpperThresSw2",
+        "Switching output 2 upper threshold",
+        "bar",
+        0.0,
+    ),
+    coef(
+        C::LowerThresSw2,
+        "LowerThresSw2",
+        "Switching output 2 lower threshold",
+        "bar",
+        0.0,
+    ),
+    coef(C::Free100, "Free100", "Customer value 100", "", 0.0),
+    coef(C::Free101, "Free101", "Customer value 101", "", 0.0),
+    coef(C::Free102, "Free102", "Customer value 102", "", 0.0),
+    coef(C::Free103, "Free103", "Customer value 103", "", 0.0),
+    coef(C::Free104, "Free104", "Customer value 104", "", 0.0),
+    coef(C::Free105, "Free105", "Customer value 105", "", 0.0),
+    coef(C::Free106, "Free106", "Customer value 106", "", 0.0),
+    coef(C::Free107, "Free107", "Customer value 107", "", 0.0),
+    coef(C::Free108, "Free108", "Customer value 108", "", 0.0),
+    coef(C::Free109, "Free109", "Customer value 109", "", 0.0),
+    coef(C::Free110, "Free110", "Customer value 110", "", 0.0),
+    coef(C::Free111, "Free111", "Customer value 111", "", 0.0),
+    nonzero(coef(
+        C::GainCondRange1,
+        "GainCondRange1",
+        "Conductivity range 1 gain",
+        "",
+        1.0,
+    )),
+    nonzero(coef(
+        C::GainCondRange2,
+        "GainCondRange2",
+        "Conductivity range 2 gain",
+        "",
+        1.0,
+    )),
+    nonzero(coef(
+        C::GainCondRange3,
+        "GainCondRange3",
+        "Conductivity range 3 gain",
+        "",
+        1.0,
+    )),
+    nonzero(coef(
+        C::GainCondRange4,
+        "GainCondRange4",
+        "Conductivity range 4 gain",
+        "",
+        1.0,
+    )),
+    coef(
+        C::ConductivityTempCoeff,
+        "ConductivityTempCoeff",
+        "Conductivity temperature coefficient",
+        "1/K",
+        0.022,
+    ),
+    nonzero(coef_min(
+        coef(
+            C::ConductivityCellConstant,
+            "ConductivityCellConstant",
+            "Conductivity cell constant",
+            "1/cm",
+            1.0,
+        ),
+        0.0,
+    )),
+    coef(
+        C::Ch0CurveP1_140,
+        "Ch0CurveP1_140",
+        "CH0 curve coefficient 140",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_141,
+        "Ch0CurveP1_141",
+        "CH0 curve coefficient 141",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_142,
+        "Ch0CurveP1_142",
+        "CH0 curve coefficient 142",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_143,
+        "Ch0CurveP1_143",
+        "CH0 curve coefficient 143",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_144,
+        "Ch0CurveP1_144",
+        "CH0 curve coefficient 144",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_145,
+        "Ch0CurveP1_145",
+        "CH0 curve coefficient 145",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_146,
+        "Ch0CurveP1_146",
+        "CH0 curve coefficient 146",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_147,
+        "Ch0CurveP1_147",
+        "CH0 curve coefficient 147",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_148,
+        "Ch0CurveP1_148",
+        "CH0 curve coefficient 148",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_149,
+        "Ch0CurveP1_149",
+        "CH0 curve coefficient 149",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_150,
+        "Ch0CurveP1_150",
+        "CH0 curve coefficient 150",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_151,
+        "Ch0CurveP1_151",
+        "CH0 curve coefficient 151",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_152,
+        "Ch0CurveP1_152",
+        "CH0 curve coefficient 152",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_153,
+        "Ch0CurveP1_153",
+        "CH0 curve coefficient 153",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_154,
+        "Ch0CurveP1_154",
+        "CH0 curve coefficient 154",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_155,
+        "Ch0CurveP1_155",
+        "CH0 curve coefficient 155",
+        "",
+        0.0,
+    ),
+    coef(
+        C::Ch0CurveP1_156,
+        "Ch0CurveP1_156",
+        "CH0 curve coefficient 156",
+        "",
+        0.0,
+    ),
+];
+
+/// Every configuration register, in ID order.
+pub static CONFIGURATION: [ConfigurationInfo; 20] = [
+    cfg(
+        K::CfgPressure,
+        "CfgPressure",
+        "Active pressure channels (bit mask)",
+        "",
+        CHANNEL_BITS,
+    ),
+    cfg(
+        K::CfgTemperature,
+        "CfgTemperature",
+        "Active temperature channels (bit mask)",
+        "",
+        CHANNEL_BITS,
+    ),
+    cfg(K::Ch0Config, "Ch0Config", "CH0 configuration", "", ANY),
+    cfg(
+        K::TempIntervalSeconds,
+        "TempIntervalSeconds",
+        "Temperature measurement interval",
+        "s",
+        ANY,
+    ),
+    cfg(K::TempComp, "TempComp", "Temperature compensation", "", ANY),
+    cfg(K::Filter, "Filter", "Low-pass filter", "", ANY),
+    cfg(K::DAC, "DAC", "Analogue output configuration", "", ANY),
+    // 0: 9600 Bd, 1: 115200 Bd; X-line devices only run 8N1.
+    cfg(K::Uart, "Uart", "Baud rate", "", Domain::Codes(&[0, 1])),
+    read_only(cfg(
+        K::FilterFactory,
+        "FilterFactory",
+        "Factory filter setting",
+        "",
+        ANY,
+    )),
+    read_only(cfg(K::Status, "Status", "Device status", "", ANY)),
+    // 0 is the broadcast address and 250 the transparent one; neither can
+    // be assigned to a device.
+    cfg(
+        K::DeviceAddress,
+        "DeviceAddress",
+        "Bus address",
+        "",
+        Domain::Range { min: 1, max: 249 },
+    ),
+    // 0: PR (vented gauge), 1: PA (sealed gauge), 2: PAA (absolute).
+    cfg(
+        K::Pmode,
+        "Pmode",
+        "Pressure mode",
+        "",
+        Domain::Codes(&[0, 1, 2]),
+    ),
+    cfg(K::SPS, "SPS", "Measurement rate", "", ANY),
+    cfg(K::SDI12, "SDI12", "SDI-12 interface", "", ON_OFF),
+    cfg(
+        K::ModbusInterframeTime9k6,
+        "ModbusInterframeTime9k6",
+        "Modbus inter-frame time at 9600 Bd",
+        "",
+        ANY,
+    ),
+    cfg(
+        K::ModbusInterframeTime115k2,
+        "ModbusInterframeTime115k2",
+        "Modbus inter-frame time at 115200 Bd",
+        "",
+        ANY,
+    ),
+    cfg(K::ConOn, "ConOn", "Conductivity measurement on", "", ON_OFF),
+    cfg(K::ConRange, "ConRange", "Conductivity range", "", ANY),
+    cfg(
+        K::ConTempCompMode,
+        "ConTempCompMode",
+        "Conductivity temperature compensation mode",
+        "",
+        ANY,
+    ),
+    read_only(cfg(
+        K::SDI12Available,
+        "SDI12Available",
+        "SDI-12 available",
+        "",
+        ON_OFF,
+    )),
+];
+
+impl Coefficients {
+    pub fn info(&self) -> &'static CoefficientInfo {
+        match COEFFICIENTS.binary_search_by_key(&(*self as u8), |info| info.id as u8) {
+            Ok(i) => &COEFFICIENTS[i],
+            // Ruled out by `every_coefficient_has_one_entry`.
+            Err(_) => unreachable!("{self:?} missing from COEFFICIENTS"),
+        }
+    }
+}
+
+impl ConfigurationCommands {
+    pub fn info(&self) -> &'static ConfigurationInfo {
+        match CONFIGURATION.binary_search_by_key(&(*self as u8), |info| info.id as u8) {
+            Ok(i) => &CONFIGURATION[i],
+            // Ruled out by `every_configuration_entry_has_one_entry`.
+            Err(_) => unreachable!("{self:?} missing from CONFIGURATION"),
+        }
+    }
+}
+
+pub fn coefficient_by_id(id: u8) -> Option<&'static CoefficientInfo> {
+    COEFFICIENTS.iter().find(|info| info.id as u8 == id)
+}
+
+/// Looks a coefficient up by variant key or human name, ignoring case.
+pub fn coefficient_by_name(name: &str) -> Option<&'static CoefficientInfo> {
+    COEFFICIENTS
+        .iter()
+        .find(|info| info.key.eq_ignore_ascii_case(name) || info.name.eq_ignore_ascii_case(name))
+}
+
+pub fn configuration_by_id(id: u8) -> Option<&'static ConfigurationInfo> {
+    CONFIGURATION.iter().find(|info| info.id as u8 == id)
+}
+
+/// Looks a configuration register up by variant key or human name,
+/// ignoring case.
+pub fn configuration_by_name(name: &str) -> Option<&'static ConfigurationInfo> {
+    CONFIGURATION
+        .iter()
+        .find(|info| info.key.eq_ignore_ascii_case(name) || info.name.eq_ignore_ascii_case(name))
+}
+
+/// A value [`CoefficientInfo::validate`] or [`ConfigurationInfo::validate`]
+/// refused to send.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ValidationError {
+    /// NaN or an infinity.
+    NotFinite {
+        coefficient: Coefficients,
+        value: f32,
+    },
+    /// Zero for an entry that divides or scales by it, e.g. a gain factor.
+    Zero(Coefficients),
+    CoefficientOutOfRange {
+        coefficient: Coefficients,
+        value: f32,
+        min: f32,
+        max: f32,
+    },
+    ConfigurationOutOfDomain {
+        entry: ConfigurationCommands,
+        value: u8,
+        domain: Domain,
+    },
+    ReadOnly(ConfigurationCommands),
+}
+
+impl core::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ValidationError::NotFinite { coefficient, value } => {
+                write!(f, "{value} is not a valid value for {coefficient:?}")
+            }
+            ValidationError::Zero(coefficient) => write!(f, "{coefficient:?} must not be zero"),
+            ValidationError::CoefficientOutOfRange {
+                coefficient,
+                value,
+                min,
+                max,
+            } => write!(f, "{coefficient:?} = {value} is outside {min}..={max}"),
+            ValidationError::ConfigurationOutOfDomain {
+                entry,
+                value,
+                domain,
+            } => write!(f, "{entry:?} = {value} is not in {domain}"),
+            ValidationError::ReadOnly(entry) => write!(f, "{entry:?} is read-only"),
+        }
+    }
+}
+
+impl CoefficientInfo {
+    /// Checks `value` against the entry's constraints.
+    pub fn validate(&self, value: f32) -> Result<(), ValidationError> {
+        if !value.is_finite() {
+            return Err(ValidationError::NotFinite {
+                coefficient: self.id,
+                value,
+            });
+        }
+        if self.nonzero && value == 0.0 {
+            return Err(ValidationError::Zero(self.id));
+        }
+        if value < self.min || value > self.max {
+            return Err(ValidationError::CoefficientOutOfRange {
+                coefficient: self.id,
+                value,
+                min: self.min,
+                max: self.max,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl ConfigurationInfo {
+    /// Checks that the entry is writable and `value` is in its domain.
+    pub fn validate(&self, value: u8) -> Result<(), ValidationError> {
+        if self.access == Access::ReadOnly {
+            return Err(ValidationError::ReadOnly(self.id));
+        }
+        if !self.domain.contains(value) {
+            return Err(ValidationError::ConfigurationOutOfDomain {
+                entry: self.id,
+                value,
+                domain: self.domain,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{COEFFICIENTS, CONFIGURATION, ValidationError};
+    use crate::base::{Coefficients, ConfigurationCommands, ProtocolError};
+    use crate::testing::{block_on, client};
+
+    #[test]
+    fn every_coefficient_has_one_entry() {
+        let mut variants = 0;
+        for id in 0..=u8::MAX {
+            let entries = COEFFICIENTS.iter().filter(|i| i.id as u8 == id).count();
+            match Coefficients::try_from(id) {
+                Ok(c) => {
+                    variants += 1;
+                    assert_eq!(entries, 1, "{c:?}");
+                    assert_eq!(c.info().id, c);
+                }
+                Err(_) => assert_eq!(entries, 0, "{id}"),
+            }
+        }
+        assert_eq!(variants, COEFFICIENTS.len());
+        assert!(
+            COEFFICIENTS
+                .windows(2)
+                .all(|w| (w[0].id as u8) < (w[1].id as u8))
+        );
+    }
+
+    #[test]
+    fn every_configuration_entry_has_one_entry() {
+        let mut variants = 0;
+        for id in 0..=u8::MAX {
+            let entries = CONFIGURATION.iter().filter(|i| i.id as u8 == id).count();
+            match ConfigurationCommands::try_from(id) {
+                Ok(c) => {
+                    variants += 1;
+                    assert_eq!(entries, 1, "{c:?}");
+                    assert_eq!(c.info().id, c);
+                }
+                Err(_) => assert_eq!(entries, 0, "{id}"),
+            }
+        }
+        assert_eq!(variants, CONFIGURATION.len());
+        assert!(
+            CONFIGURATION
+                .windows(2)
+                .all(|w| (w[0].id as u8) < (w[1].id as u8))
+        );
+    }
+
+    #[test]
+    fn out_of_domain_bytes_are_refused() {
+        use ConfigurationCommands as K;
+        let mut xline = client(24);
+        for (entry, value) in [
+            (K::Uart, 200),
+            (K::Uart, 2),
+            (K::Pmode, 3),
+            (K::DeviceAddress, 0),
+            (K::DeviceAddress, 250),
+            (K::CfgPressure, 0b0100_0000),
+            (K::SDI12, 2),
+        ] {
+            assert!(
+                matches!(
+                    block_on(xline.write_configuration(entry, value)),
+                    Err(ProtocolError::Validation(
+                        ValidationError::ConfigurationOutOfDomain { .. }
+                    ))
+                ),
+                "{entry:?} = {value}"
+            );
+        }
+        block_on(xline.write_configuration(K::Uart, 1)).unwrap();
+        block_on(xline.write_configuration(K::Pmode, 2)).unwrap();
+        block_on(xline.write_configuration(K::CfgPressure, 0b0000_0110)).unwrap();
+    }
+
+    #[test]
+    fn write_address_refuses_unassignable_addresses() {
+        let mut xline = client(24);
+        assert!(matches!(
+            block_on(xline.write_address(250)),
+            Err(ProtocolError::Validation(_))
+        ));
+        assert_eq!(block_on(xline.write_address(0)).unwrap(), 1);
+        assert_eq!(block_on(xline.write_address(249)).unwrap(), 249);
+    }
+}
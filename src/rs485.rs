@@ -0,0 +1,307 @@
+use core::time::Duration;
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::delay::DelayNs;
+
+use crate::base::XLineIO;
+
+/// Errors raised by [`Rs485Transport`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug)]
+pub enum Rs485Error<TE, PE> {
+    /// Error from the wrapped transport.
+    Transport(TE),
+    /// Error while driving the DE/RE pin.
+    Pin(PE),
+}
+
+impl<TE: core::fmt::Display, PE: core::fmt::Debug> core::fmt::Display for Rs485Error<TE, PE> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Rs485Error::Transport(e) => write!(f, "{e}"),
+            // embedded-hal pin errors only promise `Debug`.
+            Rs485Error::Pin(e) => write!(f, "driver-enable pin error: {e:?}"),
+        }
+    }
+}
+
+impl<TE, PE> core::error::Error for Rs485Error<TE, PE>
+where
+    TE: core::error::Error + 'static,
+    PE: core::fmt::Debug,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Rs485Error::Transport(e) => Some(e),
+            Rs485Error::Pin(_) => None,
+        }
+    }
+}
+
+/// Half-duplex RS-485 adapter that toggles the transceiver's driver-enable
+/// pin around every transmission.
+///
+/// DE is asserted (driven high) before `write_all`, held until the inner
+/// transport's `flush` reports transmit-complete plus `guard`, and released
+/// before any response is read. The inner transport's `flush` must block
+/// until the last stop bit has left the UART; if it cannot, size `guard` to
+/// cover the frame's time on the wire or the CRC bytes will be cut off.
+pub struct Rs485Transport<T, P, D> {
+    inner: T,
+    de: P,
+    delay: D,
+    guard: Duration,
+}
+
+impl<T, P, D> Rs485Transport<T, P, D>
+where
+    T: XLineIO,
+    P: OutputPin,
+    D: DelayNs,
+{
+    /// Wraps `inner`, releasing `de` so the bus starts in receive mode.
+    pub fn new(inner: T, mut de: P, delay: D, guard: Duration) -> Result<Self, P::Error> {
+        de.set_low()?;
+        Ok(Self {
+            inner,
+            de,
+            delay,
+            guard,
+        })
+    }
+
+    /// Time DE stays asserted after transmit-complete.
+    pub fn guard(&self) -> Duration {
+        self.guard
+    }
+
+    pub fn set_guard(&mut self, guard: Duration) {
+        self.guard = guard;
+    }
+
+    pub fn inner(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> (T, P, D) {
+        (self.inner, self.de, self.delay)
+    }
+
+    async fn transmit(&mut self, buf: &[u8], timeout: Duration) -> Result<(), T::Error> {
+        self.inner.write_all(buf, timeout).await?;
+        self.inner.flush(timeout).await?;
+        if !self.guard.is_zero() {
+            self.delay
+                .delay_us(u32::try_from(self.guard.as_micros()).unwrap_or(u32::MAX))
+                .await;
+        }
+        Ok(())
+    }
+}
+
+impl<T, P, D> XLineIO for Rs485Transport<T, P, D>
+where
+    T: XLineIO,
+    P: OutputPin,
+    D: DelayNs,
+{
+    type Error = Rs485Error<T::Error, P::Error>;
+
+    async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Self::Error> {
+        self.de.set_high().map_err(Rs485Error::Pin)?;
+        let sent = self.transmit(buf, timeout).await;
+        // Always hand the bus back, even if the transmission failed.
+        let released = self.de.set_low();
+        sent.map_err(Rs485Error::Transport)?;
+        released.map_err(Rs485Error::Pin)
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), Self::Error> {
+        self.inner
+            .read_exact(buf, timeout)
+            .await
+            .map_err(Rs485Error::Transport)
+    }
+
+    async fn clear_rx(&mut self) -> Result<(), Self::Error> {
+        self.inner.clear_rx().await.map_err(Rs485Error::Transport)
+    }
+
+    async fn flush(&mut self, timeout: Duration) -> Result<(), Self::Error> {
+        self.inner
+            .flush(timeout)
+            .await
+            .map_err(Rs485Error::Transport)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+    use core::time::Duration;
+    use std::io;
+    use std::vec::Vec;
+
+    use embedded_hal::digital::{ErrorType, OutputPin};
+    use embedded_hal_async::delay::DelayNs;
+
+    use super::{Rs485Error, Rs485Transport};
+    use crate::base::{ProtocolError, XLineIO};
+    use crate::testing::{TIMEOUT, block_on};
+
+    /// What the mocks saw, in order.
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum Op {
+        DeHigh,
+        DeLow,
+        Write,
+        Flush,
+        DelayNs(u32),
+        Read,
+    }
+
+    type Log = RefCell<Vec<Op>>;
+
+    struct Pin<'a>(&'a Log);
+
+    impl ErrorType for Pin<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Pin<'_> {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().push(Op::DeLow);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().push(Op::DeHigh);
+            Ok(())
+        }
+    }
+
+    struct Delay<'a>(&'a Log);
+
+    impl DelayNs for Delay<'_> {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.0.borrow_mut().push(Op::DelayNs(ns));
+        }
+    }
+
+    /// Transport that logs each call and fails the one equal to `fail`.
+    struct Line<'a> {
+        log: &'a Log,
+        fail: Option<Op>,
+    }
+
+    impl Line<'_> {
+        fn call(&mut self, op: Op) -> Result<(), &'static str> {
+            self.log.borrow_mut().push(op);
+            if self.fail == Some(op) {
+                return Err("line failed");
+            }
+            Ok(())
+        }
+    }
+
+    impl XLineIO for Line<'_> {
+        type Error = &'static str;
+
+        async fn write_all(&mut self, _buf: &[u8], _timeout: Duration) -> Result<(), Self::Error> {
+            self.call(Op::Write)
+        }
+
+        async fn read_exact(
+            &mut self,
+            buf: &mut [u8],
+            _timeout: Duration,
+        ) -> Result<(), Self::Error> {
+            buf.fill(0);
+            self.call(Op::Read)
+        }
+
+        async fn flush(&mut self, _timeout: Duration) -> Result<(), Self::Error> {
+            self.call(Op::Flush)
+        }
+    }
+
+    fn rs485(
+        log: &Log,
+        fail: Option<Op>,
+        guard: Duration,
+    ) -> Rs485Transport<Line<'_>, Pin<'_>, Delay<'_>> {
+        let line = Line { log, fail };
+        let rs485 = Rs485Transport::new(line, Pin(log), Delay(log), guard).unwrap();
+        assert_eq!(log.take(), [Op::DeLow]);
+        rs485
+    }
+
+    #[test]
+    fn de_is_held_through_flush_and_guard() {
+        let log = Log::default();
+        let mut rs485 = rs485(&log, None, Duration::from_micros(50));
+        block_on(rs485.write_all(&[1, 48, 0, 0], TIMEOUT)).unwrap();
+        block_on(rs485.read_exact(&mut [0; 10], TIMEOUT)).unwrap();
+        assert_eq!(
+            log.take(),
+            [
+                Op::DeHigh,
+                Op::Write,
+                Op::Flush,
+                Op::DelayNs(50_000),
+                Op::DeLow,
+                Op::Read,
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_guard_does_not_wait() {
+        let log = Log::default();
+        let mut rs485 = rs485(&log, None, Duration::ZERO);
+        block_on(rs485.write_all(&[1, 48, 0, 0], TIMEOUT)).unwrap();
+        assert_eq!(log.take(), [Op::DeHigh, Op::Write, Op::Flush, Op::DeLow]);
+    }
+
+    #[test]
+    fn de_is_released_when_the_write_fails() {
+        let log = Log::default();
+        let mut rs485 = rs485(&log, Some(Op::Write), Duration::from_micros(50));
+        assert!(matches!(
+            block_on(rs485.write_all(&[1, 48, 0, 0], TIMEOUT)),
+            Err(Rs485Error::Transport("line failed"))
+        ));
+        assert_eq!(log.take(), [Op::DeHigh, Op::Write, Op::DeLow]);
+    }
+
+    #[test]
+    fn de_is_released_when_the_flush_fails() {
+        let log = Log::default();
+        let mut rs485 = rs485(&log, Some(Op::Flush), Duration::from_micros(50));
+        assert!(matches!(
+            block_on(rs485.write_all(&[1, 48, 0, 0], TIMEOUT)),
+            Err(Rs485Error::Transport("line failed"))
+        ));
+        assert_eq!(log.take(), [Op::DeHigh, Op::Write, Op::Flush, Op::DeLow]);
+    }
+
+    fn fails() -> Result<(), ProtocolError<Rs485Error<io::Error, ()>>> {
+        Err(ProtocolError::Transport(Rs485Error::Transport(
+            io::ErrorKind::TimedOut.into(),
+        )))
+    }
+
+    #[test]
+    fn errors_convert_into_boxed_error() {
+        let run = || -> Result<(), Box<dyn std::error::Error>> { Ok(fails()?) };
+        let error = run().unwrap_err();
+        assert_eq!(error.to_string(), "transport error: timed out");
+    }
+
+    #[test]
+    fn pin_errors_display_their_debug_form() {
+        let error: Rs485Error<io::Error, ()> = Rs485Error::Pin(());
+        assert_eq!(error.to_string(), "driver-enable pin error: ()");
+    }
+}
@@ -0,0 +1,140 @@
+use core::future::Future;
+use core::time::Duration;
+use std::io;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::{
+    ClearBuffer, DataBits, Parity, SerialPort, SerialPortBuilderExt, SerialStream, StopBits,
+};
+
+use crate::base::XLineIO;
+
+/// Default line settings of an X-line device: 9600 baud, 8N1.
+pub const DEFAULT_BAUD_RATE: u32 = 9600;
+
+/// [`XLineIO`] over a `tokio_serial::SerialStream`.
+///
+/// Every operation is bounded by the `timeout` handed in by the caller and
+/// fails with [`io::ErrorKind::TimedOut`] when it elapses.
+pub struct SerialTransport {
+    port: SerialStream,
+}
+
+impl SerialTransport {
+    /// Opens `path` at `baud_rate`, 8 data bits, no parity, 1 stop bit.
+    pub fn open(path: &str, baud_rate: u32) -> io::Result<Self> {
+        let port = tokio_serial::new(path, baud_rate)
+            .data_bits(DataBits::Eight)
+            .parity(Parity::None)
+            .stop_bits(StopBits::One)
+            .open_native_async()?;
+        Ok(Self { port })
+    }
+
+    pub fn from_stream(port: SerialStream) -> Self {
+        Self { port }
+    }
+
+    /// Creates a connected pseudo-terminal pair, for exercising the transport
+    /// without hardware.
+    #[cfg(unix)]
+    pub fn pair() -> io::Result<(Self, Self)> {
+        let (a, b) = SerialStream::pair()?;
+        Ok((Self::from_stream(a), Self::from_stream(b)))
+    }
+
+    pub fn baud_rate(&self) -> io::Result<u32> {
+        Ok(self.port.baud_rate()?)
+    }
+
+    pub fn set_baud_rate(&mut self, baud_rate: u32) -> io::Result<()> {
+        Ok(self.port.set_baud_rate(baud_rate)?)
+    }
+
+    pub fn parity(&self) -> io::Result<Parity> {
+        Ok(self.port.parity()?)
+    }
+
+    pub fn set_parity(&mut self, parity: Parity) -> io::Result<()> {
+        Ok(self.port.set_parity(parity)?)
+    }
+
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+}
+
+/// Bounds `fut` by `timeout`, mapping expiry to [`io::ErrorKind::TimedOut`].
+pub(crate) async fn with_timeout<T>(
+    timeout: Duration,
+    fut: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    tokio::time::timeout(timeout, fut)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "X-line transport timed out"))?
+}
+
+impl XLineIO for SerialTransport {
+    type Error = io::Error;
+
+    async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Self::Error> {
+        with_timeout(timeout, self.port.write_all(buf)).await
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), Self::Error> {
+        with_timeout(timeout, self.port.read_exact(buf)).await?;
+        Ok(())
+    }
+
+    /// Discards everything waiting in the OS input buffer.
+    async fn clear_rx(&mut self) -> Result<(), Self::Error> {
+        Ok(self.port.clear(ClearBuffer::Input)?)
+    }
+
+    /// Waits for the OS to drain the output buffer (`tcdrain` on unix).
+    async fn flush(&mut self, timeout: Duration) -> Result<(), Self::Error> {
+        with_timeout(timeout, self.port.flush()).await
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use core::time::Duration;
+    use std::io;
+
+    use super::SerialTransport;
+    use crate::base::XLineIO;
+
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    #[tokio::test]
+    async fn pair_carries_a_request_and_its_reply() {
+        let (mut master, mut device) = SerialTransport::pair().unwrap();
+        master.write_all(&[1, 48, 0x3E, 0x00], TIMEOUT).await.unwrap();
+        master.flush(TIMEOUT).await.unwrap();
+
+        let mut request = [0u8; 4];
+        device.read_exact(&mut request, TIMEOUT).await.unwrap();
+        assert_eq!(request, [1, 48, 0x3E, 0x00]);
+        device.write_all(&[1, 48, 5, 24], TIMEOUT).await.unwrap();
+
+        let mut reply = [0u8; 4];
+        master.read_exact(&mut reply, TIMEOUT).await.unwrap();
+        assert_eq!(reply, [1, 48, 5, 24]);
+    }
+
+    #[tokio::test]
+    async fn read_times_out_when_nothing_arrives() {
+        let (mut master, _device) = SerialTransport::pair().unwrap();
+        let mut reply = [0u8; 1];
+        let error = master
+            .read_exact(&mut reply, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+}
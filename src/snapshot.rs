@@ -0,0 +1,249 @@
+use crate::base::{
+    Coefficients, ConfigurationCommands, Identity, KellerErrors, ProtocolError, SerialNumber,
+    XLineFrameError, XLineIO,
+};
+use crate::profile::{ApplyError, ApplyReport, DeviceProfile};
+use crate::{KellerXLine, XLineResult};
+
+/// Everything needed to put a device back into its current state: every
+/// coefficient and configuration register the firmware answers for, plus
+/// the identity and serial number of the device it was taken from.
+///
+/// Entries the firmware does not implement are left out rather than
+/// recorded as errors.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceSnapshot {
+    pub identity: Identity,
+    pub serial_number: SerialNumber,
+    /// Non-finite values, such as the NaN of an unset coefficient, are
+    /// stored by their bit pattern so they survive formats like JSON.
+    #[cfg_attr(feature = "serde", serde(with = "coefficient_values"))]
+    pub coefficients: Vec<(Coefficients, f32)>,
+    pub configuration: Vec<(ConfigurationCommands, u8)>,
+}
+
+#[cfg(feature = "serde")]
+mod coefficient_values {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::format;
+    use std::string::String;
+    use std::vec::Vec;
+
+    use crate::base::Coefficients;
+
+    /// A finite value as a number, anything else as `"0x…"` bits.
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(f32),
+        Bits(String),
+    }
+
+    pub fn serialize<S: Serializer>(
+        coefficients: &[(Coefficients, f32)],
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.collect_seq(coefficients.iter().map(|&(c, v)| {
+            let value = if v.is_finite() {
+                Value::Number(v)
+            } else {
+                Value::Bits(format!("{:#010x}", v.to_bits()))
+            };
+            (c, value)
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Vec<(Coefficients, f32)>, D::Error> {
+        Vec::<(Coefficients, Value)>::deserialize(d)?
+            .into_iter()
+            .map(|(c, value)| match value {
+                Value::Number(v) => Ok((c, v)),
+                Value::Bits(bits) => bits
+                    .strip_prefix("0x")
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .map(|bits| (c, f32::from_bits(bits)))
+                    .ok_or_else(|| D::Error::custom(format!("bad coefficient bits {bits:?}"))),
+            })
+            .collect()
+    }
+}
+
+impl DeviceSnapshot {
+    pub fn coefficient(&self, coefficient: Coefficients) -> Option<f32> {
+        self.coefficients
+            .iter()
+            .find(|(c, _)| *c == coefficient)
+            .map(|(_, v)| *v)
+    }
+
+    pub fn configuration(&self, entry: ConfigurationCommands) -> Option<u8> {
+        self.configuration
+            .iter()
+            .find(|(c, _)| *c == entry)
+            .map(|(_, v)| *v)
+    }
+}
+
+fn all<T: TryFrom<u8>>() -> impl Iterator<Item = T> {
+    (0..=u8::MAX).filter_map(|id| T::try_from(id).ok())
+}
+
+/// `Ok(None)` when the firmware does not implement the entry.
+pub(crate) fn supported<T, E>(result: XLineResult<T, E>) -> XLineResult<Option<T>, E> {
+    match result {
+        Ok(v) => Ok(Some(v)),
+        Err(ProtocolError::FrameError(XLineFrameError::DeviceError(
+            KellerErrors::InvalidAddress,
+        )))
+        | Err(ProtocolError::Unsupported(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    /// Reads identity, serial number and every known coefficient and
+    /// configuration register into a [`DeviceSnapshot`].
+    pub async fn snapshot(&mut self) -> XLineResult<DeviceSnapshot, T::Error> {
+        let identity = self.init_and_release().await?;
+        let serial_number = self.read_serial_number().await?;
+        let mut coefficients = Vec::new();
+        for c in all::<Coefficients>() {
+            if let Some(v) = supported(self.read_coefficent(c).await)? {
+                coefficients.push((c, v));
+            }
+        }
+        let mut configuration = Vec::new();
+        for entry in all::<ConfigurationCommands>() {
+            if let Some(v) = supported(self.read_configuration(entry).await)? {
+                configuration.push((entry, v));
+            }
+        }
+        Ok(DeviceSnapshot {
+            identity,
+            serial_number,
+            coefficients,
+            configuration,
+        })
+    }
+
+    /// Writes back every value in `snapshot` that differs from the device,
+    /// reading each one back to verify it. Cloning a reference sensor onto a
+    /// replacement is a restore of the reference's snapshot.
+    ///
+    /// The device must run the same firmware class and group as the one the
+    /// snapshot came from, since some IDs change meaning between variants.
+    /// Entries the device does not implement, e.g. on a sibling firmware
+    /// revision, are skipped and counted in [`ApplyReport::unsupported`].
+    /// Values are written without validation, since they were read from a
+    /// device. As with [`KellerXLine::plan`], the device address and
+    /// read-only entries are never written, so a clone keeps its own; they
+    /// are not counted as unchanged either.
+    pub async fn restore(
+        &mut self,
+        snapshot: &DeviceSnapshot,
+    ) -> Result<ApplyReport, ApplyError<T::Error>> {
+        let identity = self.init_and_release().await?;
+        if (identity.class, identity.group) != (snapshot.identity.class, snapshot.identity.group) {
+            return Err(ApplyError::IdentityMismatch {
+                expected: snapshot.identity,
+                actual: identity,
+            });
+        }
+        let profile = DeviceProfile::from(snapshot);
+        let planned = self.plan_entries(&profile, true).await?;
+        // Recorded values go back as they were, even NaN for an unset
+        // coefficient, so they are not validated.
+        let mut report = self.apply_changes(&planned.plan, false, true).await?;
+        report.unchanged += planned.unchanged;
+        report.unsupported = planned.unsupported;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::KellerXLine;
+    use crate::base::{Coefficients, ConfigurationCommands};
+    use crate::emulator::XLineDevice;
+    use crate::testing::{TIMEOUT, block_on, client, identity};
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn nan_coefficients_survive_json() {
+        let mut xline = client(24);
+        let mut snapshot = block_on(xline.snapshot()).unwrap();
+        snapshot.coefficients[0].1 = f32::NAN;
+        snapshot.coefficients[1].1 = f32::from_bits(0x7fc0_1234);
+        snapshot.coefficients[2].1 = f32::NEG_INFINITY;
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let back: super::DeviceSnapshot = serde_json::from_str(&json).unwrap();
+        for (a, b) in snapshot.coefficients.iter().zip(&back.coefficients) {
+            assert_eq!((a.0, a.1.to_bits()), (b.0, b.1.to_bits()));
+        }
+        assert_eq!(back.configuration, snapshot.configuration);
+    }
+
+    #[test]
+    fn restore_skips_entries_the_target_does_not_implement() {
+        let mut reference = client(24);
+        block_on(reference.write_configuration(ConfigurationCommands::Filter, 3)).unwrap();
+        let snapshot = block_on(reference.snapshot()).unwrap();
+
+        // A sibling revision without the first coefficient the reference has.
+        let mut device = XLineDevice::new(1, identity(24), 2);
+        device.set_coefficient(snapshot.coefficients[0].0, None);
+        let target = KellerXLine::new(device, TIMEOUT, 1).unwrap();
+        let mut target = block_on(target.init()).map_err(|e| e.error).unwrap();
+        let report = block_on(target.restore(&snapshot)).unwrap();
+        assert_eq!(report.unsupported, 1);
+        assert_eq!(
+            block_on(target.read_configuration(ConfigurationCommands::Filter)).unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    fn restore_writes_unset_coefficients_back() {
+        let mut reference = client(24);
+        let mut snapshot = block_on(reference.snapshot()).unwrap();
+        for (coefficient, value) in snapshot.coefficients.iter_mut() {
+            if *coefficient == Coefficients::Free100 {
+                *value = f32::NAN;
+            }
+        }
+
+        let mut target = client(24);
+        block_on(target.write_coefficent(Coefficients::Free100, 1.0)).unwrap();
+        let report = block_on(target.restore(&snapshot)).unwrap();
+        assert_eq!(report.written.len(), 1);
+        assert!(
+            block_on(target.read_coefficent(Coefficients::Free100))
+                .unwrap()
+                .is_nan()
+        );
+    }
+
+    #[test]
+    fn restore_counts_only_compared_entries_as_unchanged() {
+        let mut xline = client(24);
+        let snapshot = block_on(xline.snapshot()).unwrap();
+        let report = block_on(xline.restore(&snapshot)).unwrap();
+        let skipped = snapshot
+            .configuration
+            .iter()
+            .filter(|(entry, _)| {
+                *entry == ConfigurationCommands::DeviceAddress
+                    || entry.info().access == crate::registry::Access::ReadOnly
+            })
+            .count();
+        assert!(report.written.is_empty());
+        assert_eq!(
+            report.unchanged,
+            snapshot.coefficients.len() + snapshot.configuration.len() - skipped
+        );
+    }
+}
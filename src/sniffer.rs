@@ -0,0 +1,530 @@
+use core::fmt;
+use core::time::Duration;
+use std::string::String;
+use std::vec::Vec;
+
+use crate::TRANSPARENT_ADDRESS;
+use crate::base::{
+    Channels, Coefficients, ConfigurationCommands, FunctionCodes, Identity, KellerErrors,
+    SerialNumber, ZeroCommands, crc16, f32_from_be_bytes,
+};
+
+/// What a decoded frame is.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrameRole {
+    Request,
+    Response,
+    /// Bytes that do not form a valid frame (noise, collisions, truncation).
+    Garbage,
+}
+
+/// One frame seen on the bus, with a human-readable interpretation.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedFrame {
+    /// Timestamp of the frame's first byte, in microseconds.
+    pub t_us: u64,
+    pub role: FrameRole,
+    pub address: u8,
+    pub function: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::capture::hex_bytes"))]
+    pub raw: Vec<u8>,
+    pub meaning: String,
+}
+
+/// A request and the response that answered it. Either side can be missing:
+/// unanswered requests, orphan responses and garbage are reported too.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub request: Option<DecodedFrame>,
+    pub response: Option<DecodedFrame>,
+}
+
+impl Exchange {
+    /// Time from the start of the request to the start of the response;
+    /// `None` if either is missing or the timestamps run backwards.
+    pub fn latency_us(&self) -> Option<u64> {
+        self.response
+            .as_ref()?
+            .t_us
+            .checked_sub(self.request.as_ref()?.t_us)
+    }
+
+    /// The exchange as one line of JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("exchange serializes")
+    }
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first = self.request.as_ref().or(self.response.as_ref());
+        let Some(first) = first else {
+            return Ok(());
+        };
+        write!(
+            f,
+            "{:>12.6} @{:<3} ",
+            first.t_us as f64 / 1e6,
+            first.address
+        )?;
+        if first.role == FrameRole::Garbage {
+            return write!(f, "?? {}", first.meaning);
+        }
+        write!(f, "F{:<3} ", first.function & 0x7F)?;
+        match (&self.request, &self.response) {
+            (Some(req), Some(resp)) => {
+                write!(f, "{} -> {}", req.meaning, resp.meaning)?;
+                if let Some(latency) = self.latency_us() {
+                    write!(f, " [{:.1} ms]", latency as f64 / 1000.0)?;
+                }
+                Ok(())
+            }
+            (Some(req), None) => write!(f, "{} -> (no response)", req.meaning),
+            (None, Some(resp)) => write!(f, "(no request) -> {}", resp.meaning),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+enum Candidate {
+    Frame(usize, FrameRole),
+    Incomplete,
+    Garbage,
+}
+
+/// Decodes a passively captured X-line byte stream into request/response
+/// exchanges.
+///
+/// Feed it bytes as they arrive with their receive timestamps. Frames are
+/// delimited by CRC and by the lengths in [`FunctionCodes`]; a pause longer
+/// than the inter-frame gap also ends any partial frame, so a corrupted
+/// frame does not swallow the one after it.
+pub struct Sniffer {
+    buf: Vec<u8>,
+    times: Vec<u64>,
+    last_us: Option<u64>,
+    gap_us: u64,
+    pending: Option<DecodedFrame>,
+    out: Vec<Exchange>,
+}
+
+impl Default for Sniffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sniffer {
+    /// Inter-frame gap suited to 9600 baud (about 3.5 character times).
+    pub const DEFAULT_GAP: Duration = Duration::from_millis(4);
+
+    pub fn new() -> Self {
+        Self::with_gap(Self::DEFAULT_GAP)
+    }
+
+    pub fn with_gap(gap: Duration) -> Self {
+        Self {
+            buf: Vec::new(),
+            times: Vec::new(),
+            last_us: None,
+            gap_us: gap.as_micros() as u64,
+            pending: None,
+            out: Vec::new(),
+        }
+    }
+
+    /// Adds bytes received at `t_us` and returns the exchanges completed so far.
+    pub fn feed(&mut self, t_us: u64, bytes: &[u8]) -> Vec<Exchange> {
+        if let Some(last) = self.last_us
+            && t_us.saturating_sub(last) > self.gap_us
+        {
+            self.drain(true);
+        }
+        if !bytes.is_empty() {
+            self.last_us = Some(t_us);
+        }
+        self.buf.extend_from_slice(bytes);
+        self.times.extend(core::iter::repeat_n(t_us, bytes.len()));
+        self.drain(false);
+        core::mem::take(&mut self.out)
+    }
+
+    /// Flushes everything still buffered, e.g. at the end of a capture.
+    pub fn finish(&mut self) -> Vec<Exchange> {
+        self.drain(true);
+        if let Some(req) = self.pending.take() {
+            self.out.push(Exchange {
+                request: Some(req),
+                response: None,
+            });
+        }
+        self.last_us = None;
+        core::mem::take(&mut self.out)
+    }
+
+    fn expecting_response(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    fn drain(&mut self, end_of_frame: bool) {
+        let mut garbage = 0usize;
+        loop {
+            let candidate = self.candidate(garbage);
+            let (end, role) = match candidate {
+                Candidate::Frame(end, role) => (end, role),
+                Candidate::Incomplete if !end_of_frame => break,
+                Candidate::Incomplete | Candidate::Garbage => {
+                    if garbage >= self.buf.len() {
+                        break;
+                    }
+                    garbage += 1;
+                    continue;
+                }
+            };
+            self.emit_garbage(garbage);
+            let frame = self.take_frame(end - garbage, role);
+            garbage = 0;
+            self.push_frame(frame);
+        }
+        if end_of_frame {
+            self.emit_garbage(self.buf.len());
+        }
+    }
+
+    /// Classifies the bytes starting at `at`.
+    fn candidate(&self, at: usize) -> Candidate {
+        let buf = &self.buf[at..];
+        if buf.len() < 2 {
+            return Candidate::Incomplete;
+        }
+        let func = buf[1];
+        let mut lens: Vec<(usize, FrameRole)> = Vec::new();
+        if func > 127 {
+            lens.push((5, FrameRole::Response));
+        } else if let Ok(code) = FunctionCodes::try_from(func) {
+            let response = (code.response_len(), FrameRole::Response);
+            let requests = code.request_lens().iter().map(|l| (*l, FrameRole::Request));
+            if self.expecting_response() {
+                lens.push(response);
+                lens.extend(requests);
+            } else {
+                lens.extend(requests);
+                lens.push(response);
+            }
+        } else {
+            return Candidate::Garbage;
+        }
+        let mut incomplete = false;
+        for (len, role) in lens {
+            if buf.len() < len {
+                incomplete = true;
+                continue;
+            }
+            let crc = u16::from_be_bytes([buf[len - 2], buf[len - 1]]);
+            if crc == crc16(&buf[..len - 2]) {
+                return Candidate::Frame(at + len, role);
+            }
+        }
+        if incomplete {
+            Candidate::Incomplete
+        } else {
+            Candidate::Garbage
+        }
+    }
+
+    fn take_frame(&mut self, end: usize, role: FrameRole) -> DecodedFrame {
+        let raw: Vec<u8> = self.buf.drain(..end).collect();
+        let t_us = self.times.drain(..end).next().unwrap_or(0);
+        let meaning = match role {
+            FrameRole::Request => describe_request(&raw),
+            FrameRole::Response => describe_response(&raw, self.pending.as_ref()),
+            FrameRole::Garbage => String::new(),
+        };
+        DecodedFrame {
+            t_us,
+            role,
+            address: raw[0],
+            function: raw[1],
+            raw,
+            meaning,
+        }
+    }
+
+    fn emit_garbage(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let raw: Vec<u8> = self.buf.drain(..len).collect();
+        let t_us = self.times.drain(..len).next().unwrap_or(0);
+        let meaning = format!("{} unframed byte(s): {:02x?}", raw.len(), raw);
+        self.out.push(Exchange {
+            request: Some(DecodedFrame {
+                t_us,
+                role: FrameRole::Garbage,
+                address: raw[0],
+                function: raw.get(1).copied().unwrap_or(0),
+                raw,
+                meaning,
+            }),
+            response: None,
+        });
+    }
+
+    fn push_frame(&mut self, frame: DecodedFrame) {
+        match frame.role {
+            FrameRole::Request => {
+                if let Some(unanswered) = self.pending.replace(frame) {
+                    self.out.push(Exchange {
+                        request: Some(unanswered),
+                        response: None,
+                    });
+                }
+            }
+            FrameRole::Response => {
+                let answers = self.pending.as_ref().is_some_and(|req| {
+                    req.function == frame.function & 0x7F
+                        && (req.address == frame.address || req.address == TRANSPARENT_ADDRESS)
+                });
+                let request = if answers { self.pending.take() } else { None };
+                self.out.push(Exchange {
+                    request,
+                    response: Some(frame),
+                });
+            }
+            FrameRole::Garbage => {}
+        }
+    }
+}
+
+fn coefficient_name(id: u8) -> String {
+    match Coefficients::try_from(id) {
+        Ok(c) => format!("{id} ({})", c.info().name),
+        Err(()) => format!("{id} (unknown)"),
+    }
+}
+
+fn configuration_name(id: u8) -> String {
+    match ConfigurationCommands::try_from(id) {
+        Ok(c) => format!("{id} ({})", c.info().name),
+        Err(()) => format!("{id} (unknown)"),
+    }
+}
+
+/// Address, function code and CRC: anything shorter is not a frame.
+const MIN_FRAME_LEN: usize = 4;
+
+fn short_frame(raw: &[u8]) -> String {
+    format!("short frame ({} byte(s)): {raw:02x?}", raw.len())
+}
+
+fn channel_name(id: u8) -> String {
+    match Channels::try_from(id) {
+        Ok(c) => format!("{c:?}"),
+        Err(()) => format!("channel {id} (unknown)"),
+    }
+}
+
+fn float(data: &[u8]) -> String {
+    match data.get(..4).and_then(f32_from_be_bytes) {
+        Some(v) => format!("{v}"),
+        None => String::from("?"),
+    }
+}
+
+/// Interprets a request frame (address and CRC included).
+pub fn describe_request(raw: &[u8]) -> String {
+    if raw.len() < MIN_FRAME_LEN {
+        return short_frame(raw);
+    }
+    let data = &raw[2..raw.len() - 2];
+    let Ok(func) = FunctionCodes::try_from(raw[1]) else {
+        return format!("unknown function {}", raw[1]);
+    };
+    let arg = data.first().copied().unwrap_or(0);
+    match func {
+        FunctionCodes::ReadCoefficients => format!("read coefficient {}", coefficient_name(arg)),
+        FunctionCodes::WriteCoefficients => format!(
+            "write coefficient {} = {}",
+            coefficient_name(arg),
+            float(data.get(1..).unwrap_or_default())
+        ),
+        FunctionCodes::ReadConfigurations => {
+            format!("read configuration {}", configuration_name(arg))
+        }
+        FunctionCodes::WriteConfiguration => format!(
+            "write configuration {} = {}",
+            configuration_name(arg),
+            data.get(1).copied().unwrap_or(0)
+        ),
+        FunctionCodes::InitializeAndRealese => String::from("initialise and release"),
+        FunctionCodes::WriteAndReadNewDeviceAddress => match data.first() {
+            Some(&a) if a != 0 => format!("set device address to {a}"),
+            _ => String::from("read device address"),
+        },
+        FunctionCodes::ReadSerialNumber => String::from("read serial number"),
+        FunctionCodes::ReadChannelValueFloat => format!("read {} (float)", channel_name(arg)),
+        FunctionCodes::ReadChannelValueInteger => format!("read {} (integer)", channel_name(arg)),
+        FunctionCodes::ZeroCommand => {
+            let command = match ZeroCommands::try_from(arg) {
+                Ok(c) => format!("{c:?}"),
+                Err(()) => format!("zero command {arg} (unknown)"),
+            };
+            if data.len() >= 5 {
+                format!("{command} to {}", float(&data[1..5]))
+            } else {
+                command
+            }
+        }
+        FunctionCodes::ReadConfigurationBlock => format!("read configuration block {arg}"),
+    }
+}
+
+/// Interprets a response frame, using the request it answers when known.
+pub fn describe_response(raw: &[u8], request: Option<&DecodedFrame>) -> String {
+    if raw.len() < MIN_FRAME_LEN {
+        return short_frame(raw);
+    }
+    let data = &raw[2..raw.len() - 2];
+    if raw[1] > 127 {
+        return match data.first() {
+            Some(&code) => format!("error: {}", KellerErrors::from(code)),
+            None => String::from("error: no code"),
+        };
+    }
+    let Ok(func) = FunctionCodes::try_from(raw[1]) else {
+        return format!("unknown function {}", raw[1]);
+    };
+    let status = || match data.get(4) {
+        Some(s) => format!(" (status 0x{s:02x})"),
+        None => String::new(),
+    };
+    match func {
+        FunctionCodes::ReadCoefficients | FunctionCodes::ReadChannelValueFloat => {
+            format!("{}{}", float(data), status())
+        }
+        FunctionCodes::ReadChannelValueInteger => match data.get(..4) {
+            Some(b) => format!(
+                "{}{}",
+                i32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+                status()
+            ),
+            None => String::from("?"),
+        },
+        FunctionCodes::ReadConfigurations => {
+            format!("{}", data.first().copied().unwrap_or(0))
+        }
+        FunctionCodes::WriteCoefficients
+        | FunctionCodes::WriteConfiguration
+        | FunctionCodes::ZeroCommand => String::from("ok"),
+        FunctionCodes::InitializeAndRealese => match Identity::from_payload(data) {
+            Some(id) => format!(
+                "firmware {id}, buffer {}, {}",
+                id.buffer,
+                if id.was_restarted() {
+                    "restarted"
+                } else {
+                    "already initialised"
+                }
+            ),
+            None => String::from("?"),
+        },
+        FunctionCodes::WriteAndReadNewDeviceAddress => {
+            format!("address {}", data.first().copied().unwrap_or(0))
+        }
+        FunctionCodes::ReadSerialNumber => match SerialNumber::from_payload(data) {
+            Some(serial) => format!("serial {serial}"),
+            None => String::from("?"),
+        },
+        FunctionCodes::ReadConfigurationBlock => {
+            let index = request
+                .and_then(|r| r.raw.get(2))
+                .map(|i| format!("block {i}: "))
+                .unwrap_or_default();
+            format!("{index}{data:02x?}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodedFrame, Exchange, FrameRole, Sniffer, describe_request, describe_response};
+    use crate::base::crc16_hi_lo;
+    use std::string::String;
+    use std::vec::Vec;
+
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut raw = body.to_vec();
+        let (hi, lo) = crc16_hi_lo(body);
+        raw.extend_from_slice(&[hi, lo]);
+        raw
+    }
+
+    fn decoded(t_us: u64, role: FrameRole) -> DecodedFrame {
+        DecodedFrame {
+            t_us,
+            role,
+            address: 1,
+            function: 48,
+            raw: Vec::new(),
+            meaning: String::new(),
+        }
+    }
+
+    #[test]
+    fn short_frames_are_described_not_panicked_on() {
+        for len in 0..4 {
+            let raw = &[1, 30, 7][..len.min(3)];
+            assert!(describe_request(raw).starts_with("short frame"));
+            assert!(describe_response(raw, None).starts_with("short frame"));
+        }
+    }
+
+    #[test]
+    fn truncated_payloads_do_not_panic() {
+        let raw = frame(&[1, 31, 64]);
+        assert_eq!(describe_request(&raw), "write coefficient 64 (P1 offset) = ?");
+    }
+
+    #[test]
+    fn latency_is_none_when_timestamps_run_backwards() {
+        let exchange = Exchange {
+            request: Some(decoded(2_000, FrameRole::Request)),
+            response: Some(decoded(1_000, FrameRole::Response)),
+        };
+        assert_eq!(exchange.latency_us(), None);
+    }
+
+    #[test]
+    fn names_come_from_the_registry() {
+        let raw = frame(&[1, 30, 65]);
+        assert_eq!(describe_request(&raw), "read coefficient 65 (P1 gain)");
+        let raw = frame(&[1, 32, 13]);
+        assert_eq!(describe_request(&raw), "read configuration 13 (Bus address)");
+    }
+
+    #[test]
+    fn exceptions_use_the_error_display() {
+        let raw = frame(&[1, 30 | 0x80, 32]);
+        assert_eq!(
+            describe_response(&raw, None),
+            "error: device not initialised (32)"
+        );
+    }
+
+    #[test]
+    fn decodes_an_exchange_from_the_stream() {
+        let mut sniffer = Sniffer::new();
+        let mut exchanges = sniffer.feed(0, &frame(&[1, 67]));
+        exchanges.extend(sniffer.feed(5_000, &frame(&[1, 67, 0, 0x3D, 0x09, 0x7B])));
+        assert_eq!(exchanges.len(), 1);
+        assert_eq!(
+            std::format!("{}", exchanges[0]),
+            "    0.000000 @1   F67  read serial number -> serial 4000123 [5.0 ms]"
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use crate::KellerXLine;
+use crate::base::{Channels, ProtocolError, XLineIO};
+
+/// Largest window [`StabilityWindow`] can hold.
+pub const MAX_WINDOW: usize = 64;
+
+/// Sliding window over the most recent readings of a channel.
+#[derive(Debug, Clone)]
+pub struct StabilityWindow {
+    samples: [f32; MAX_WINDOW],
+    window: usize,
+    len: usize,
+    next: usize,
+}
+
+impl StabilityWindow {
+    /// `window` is clamped to `2..=MAX_WINDOW` readings.
+    pub fn new(window: usize) -> Self {
+        Self {
+            samples: [0.0; MAX_WINDOW],
+            window: window.clamp(2, MAX_WINDOW),
+            len: 0,
+            next: 0,
+        }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.samples[self.next] = value;
+        self.next = (self.next + 1) % self.window;
+        self.len = (self.len + 1).min(self.window);
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.window
+    }
+
+    fn values(&self) -> &[f32] {
+        &self.samples[..self.len]
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.values().iter().sum::<f32>() / self.len.max(1) as f32
+    }
+
+    /// Sample standard deviation of the readings in the window.
+    pub fn std_dev(&self) -> f32 {
+        if self.len < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let sum: f32 = self.values().iter().map(|v| (v - mean) * (v - mean)).sum();
+        sqrt(sum / (self.len - 1) as f32)
+    }
+
+    /// The window is full and its spread is at most `max_std_dev`.
+    pub fn is_stable(&self, max_std_dev: f32) -> bool {
+        self.is_full() && self.std_dev() <= max_std_dev
+    }
+
+    pub fn reading(&self) -> StableReading {
+        StableReading {
+            mean: self.mean(),
+            std_dev: self.std_dev(),
+            samples: self.len as u16,
+        }
+    }
+}
+
+/// `f32::sqrt` is not available without std; three Newton steps from a
+/// bit-level first guess are plenty for a noise figure.
+fn sqrt(x: f32) -> f32 {
+    if x.is_nan() || x <= 0.0 {
+        return 0.0;
+    }
+    let mut y = f32::from_bits((x.to_bits() >> 1) + 0x1fbd_1df5);
+    for _ in 0..3 {
+        y = 0.5 * (y + x / y);
+    }
+    y
+}
+
+/// Mean and spread of a window of readings that met the stability limit.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StableReading {
+    pub mean: f32,
+    pub std_dev: f32,
+    pub samples: u16,
+}
+
+/// Errors raised by [`KellerXLine::read_channel_stable`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug)]
+pub enum StabilityError<TE> {
+    Protocol(ProtocolError<TE>),
+    /// The readings did not settle within the sample budget; `std_dev` is
+    /// the spread of the last full window.
+    NotStable {
+        std_dev: f32,
+    },
+}
+
+impl<TE> From<ProtocolError<TE>> for StabilityError<TE> {
+    fn from(e: ProtocolError<TE>) -> Self {
+        StabilityError::Protocol(e)
+    }
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    /// Reads `channel` back to back until the last `window` readings have a
+    /// standard deviation of at most `max_std_dev`, giving up after
+    /// `max_samples` readings.
+    pub async fn read_channel_stable(
+        &mut self,
+        channel: Channels,
+        window: usize,
+        max_std_dev: f32,
+        max_samples: usize,
+    ) -> Result<StableReading, StabilityError<T::Error>> {
+        let mut readings = StabilityWindow::new(window);
+        for _ in 0..max_samples {
+            readings.push(self.read_channel_value(channel).await?);
+            if readings.is_stable(max_std_dev) {
+                return Ok(readings.reading());
+            }
+        }
+        Err(StabilityError::NotStable {
+            std_dev: readings.std_dev(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{MAX_WINDOW, StabilityError, StabilityWindow, sqrt};
+    use crate::KellerXLine;
+    use crate::base::{Channels, XLineIO};
+    use crate::emulator::XLineDevice;
+    use crate::testing::{TIMEOUT, block_on, identity};
+
+    /// Emulator whose P1 reads the next scripted value on every request.
+    struct Drifting {
+        device: XLineDevice,
+        readings: &'static [f32],
+        requests: usize,
+    }
+
+    impl XLineIO for Drifting {
+        type Error = <XLineDevice as XLineIO>::Error;
+
+        async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Self::Error> {
+            let reading = self.readings[self.requests.min(self.readings.len() - 1)];
+            self.device.set_raw(Channels::P1, Some(reading));
+            self.requests += 1;
+            self.device.write_all(buf, timeout).await
+        }
+
+        async fn read_exact(
+            &mut self,
+            buf: &mut [u8],
+            timeout: Duration,
+        ) -> Result<(), Self::Error> {
+            self.device.read_exact(buf, timeout).await
+        }
+    }
+
+    fn drifting(readings: &'static [f32]) -> KellerXLine<Drifting> {
+        let device = Drifting {
+            device: XLineDevice::new(1, identity(24), 4_000_123),
+            readings,
+            requests: 0,
+        };
+        let xline = KellerXLine::new(device, TIMEOUT, 1).unwrap();
+        let mut xline = block_on(xline.init()).map_err(|e| e.error).unwrap();
+        xline.transport_mut().requests = 0;
+        xline
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= b.abs() * 1e-6
+    }
+
+    #[test]
+    fn sqrt_converges() {
+        for (x, root) in [
+            (4.0, 2.0),
+            (2.0, core::f32::consts::SQRT_2),
+            (1e-6, 1e-3),
+            (19.0, 4.358_899),
+            (1e6, 1e3),
+        ] {
+            assert!(close(sqrt(x), root), "sqrt({x}) = {}", sqrt(x));
+        }
+        assert_eq!(sqrt(0.0), 0.0);
+        assert_eq!(sqrt(-1.0), 0.0);
+        assert_eq!(sqrt(f32::NAN), 0.0);
+    }
+
+    #[test]
+    fn window_keeps_the_most_recent_readings() {
+        let mut window = StabilityWindow::new(3);
+        window.push(1.0);
+        assert_eq!(window.std_dev(), 0.0);
+        window.push(2.0);
+        assert!(!window.is_full());
+        window.push(3.0);
+        assert!(window.is_full());
+        assert_eq!(window.mean(), 2.0);
+        assert!(close(window.std_dev(), 1.0));
+
+        // 10 replaces 1: the window holds 2, 3, 10, so the squared
+        // deviations from 5 sum to 38 and the variance is 19.
+        window.push(10.0);
+        assert_eq!(window.mean(), 5.0);
+        assert!(close(window.std_dev(), 4.358_899));
+        assert_eq!(window.reading().samples, 3);
+        assert!(!window.is_stable(4.0));
+        assert!(window.is_stable(5.0));
+
+        window.clear();
+        assert!(!window.is_full());
+        assert_eq!(window.mean(), 0.0);
+    }
+
+    #[test]
+    fn window_size_is_clamped() {
+        let mut small = StabilityWindow::new(0);
+        small.push(1.0);
+        small.push(1.0);
+        assert!(small.is_full());
+
+        let mut large = StabilityWindow::new(1000);
+        for _ in 1..MAX_WINDOW {
+            large.push(1.0);
+        }
+        assert!(!large.is_full());
+        large.push(1.0);
+        assert!(large.is_full());
+    }
+
+    #[test]
+    fn stable_reading_is_taken_from_the_last_window() {
+        // Settles on the fourth reading: 1, 1.25, 0.75 have a variance of
+        // 0.125 / 2, i.e. a standard deviation of 0.25.
+        let mut xline = drifting(&[5.0, 1.0, 1.25, 0.75, 9.0]);
+        let reading = block_on(xline.read_channel_stable(Channels::P1, 3, 0.3, 10)).unwrap();
+        assert_eq!(reading.mean, 1.0);
+        assert!(close(reading.std_dev, 0.25));
+        assert_eq!(reading.samples, 3);
+        assert_eq!(xline.transport_mut().requests, 4);
+    }
+
+    #[test]
+    fn unsettled_readings_give_up_after_max_samples() {
+        let mut xline = drifting(&[0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+        let result = block_on(xline.read_channel_stable(Channels::P1, 3, 0.1, 6));
+        // The last window holds 0, 1, 0 (or 1, 0, 1): variance 1/3.
+        match result {
+            Err(StabilityError::NotStable { std_dev }) => assert!(close(std_dev, 0.577_350_3)),
+            other => panic!("expected NotStable, got {other:?}"),
+        }
+        assert_eq!(xline.transport_mut().requests, 6);
+    }
+}
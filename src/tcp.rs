@@ -0,0 +1,217 @@
+use core::time::Duration;
+use std::io;
+use std::string::String;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::base::XLineIO;
+use crate::serial::with_timeout;
+
+/// [`XLineIO`] over a raw TCP socket, for Ethernet-to-RS485 serial-device
+/// servers running in "raw socket" / "TCP server" mode.
+///
+/// The connection is opened lazily and dropped after an I/O error; the next
+/// transaction reconnects, so a gateway reboot costs one failed request
+/// rather than the whole session. A timeout keeps the connection: it usually
+/// means the addressed device is absent, and `clear_rx` discards a reply
+/// that turns up late.
+pub struct TcpTransport {
+    addr: String,
+    stream: Option<TcpStream>,
+    connect_timeout: Duration,
+    flush_stale: bool,
+}
+
+impl TcpTransport {
+    /// Creates a transport for `addr` (`host:port`) without connecting yet.
+    pub fn new(addr: impl Into<String>, connect_timeout: Duration) -> Self {
+        Self {
+            addr: addr.into(),
+            stream: None,
+            connect_timeout,
+            flush_stale: true,
+        }
+    }
+
+    /// Creates a transport and opens the connection immediately.
+    pub async fn connect(addr: impl Into<String>, connect_timeout: Duration) -> io::Result<Self> {
+        let mut transport = Self::new(addr, connect_timeout);
+        transport.reconnect().await?;
+        Ok(transport)
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Whether `clear_rx` discards bytes already waiting on the socket, such
+    /// as a late reply to a request that previously timed out. On by default.
+    pub fn set_flush_stale(&mut self, flush_stale: bool) {
+        self.flush_stale = flush_stale;
+    }
+
+    /// Drops the current connection, if any, and opens a new one.
+    pub async fn reconnect(&mut self) -> io::Result<()> {
+        self.stream = None;
+        let stream =
+            with_timeout(self.connect_timeout, TcpStream::connect(self.addr.as_str())).await?;
+        stream.set_nodelay(true)?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self) {
+        self.stream = None;
+    }
+
+    async fn stream(&mut self) -> io::Result<&mut TcpStream> {
+        if self.stream.is_none() {
+            self.reconnect().await?;
+        }
+        Ok(self.stream.as_mut().expect("connected above"))
+    }
+
+    /// Forgets the connection when `res` failed with anything but a timeout
+    /// so the next call reconnects.
+    fn check<T>(&mut self, res: io::Result<T>) -> io::Result<T> {
+        if let Err(e) = &res
+            && e.kind() != io::ErrorKind::TimedOut
+        {
+            self.stream = None;
+        }
+        res
+    }
+}
+
+impl XLineIO for TcpTransport {
+    type Error = io::Error;
+
+    async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Self::Error> {
+        let stream = self.stream().await?;
+        let res = with_timeout(timeout, stream.write_all(buf)).await;
+        self.check(res)
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), Self::Error> {
+        let stream = self.stream().await?;
+        let res = with_timeout(timeout, stream.read_exact(buf)).await;
+        self.check(res).map(|_| ())
+    }
+
+    async fn clear_rx(&mut self) -> Result<(), Self::Error> {
+        if !self.flush_stale {
+            return Ok(());
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(());
+        };
+        let mut scratch = [0u8; 64];
+        loop {
+            match stream.try_read(&mut scratch) {
+                // Peer closed: reconnect on the next write.
+                Ok(0) => {
+                    self.stream = None;
+                    return Ok(());
+                }
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return self.check(Err(e)),
+            }
+        }
+    }
+
+    async fn flush(&mut self, timeout: Duration) -> Result<(), Self::Error> {
+        let stream = self.stream().await?;
+        let res = with_timeout(timeout, stream.flush()).await;
+        self.check(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::io;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::TcpTransport;
+    use crate::base::{Identity, ProtocolError};
+    use crate::emulator::XLineDevice;
+    use crate::{KellerXLine, Uninitialized};
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    /// Serves an emulated device at address 1, one request per read, and
+    /// counts the connections it accepted.
+    async fn gateway() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            let identity = Identity::from_payload(&[5, 24, 19, 3, 10, 0]).unwrap();
+            let mut device = XLineDevice::new(1, identity, 4_000_123);
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut request = [0u8; 64];
+                let mut reply = [0u8; 64];
+                while let Ok(n @ 1..) = socket.read(&mut request).await {
+                    let len = device.process(&request[..n], &mut reply);
+                    if socket.write_all(&reply[..len]).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        (addr, accepted)
+    }
+
+    fn client(addr: String, address: u8) -> KellerXLine<TcpTransport, Uninitialized> {
+        KellerXLine::new(TcpTransport::new(addr, TIMEOUT), TIMEOUT, address).unwrap()
+    }
+
+    #[tokio::test]
+    async fn talks_to_a_device_behind_the_gateway() {
+        let (addr, _) = gateway().await;
+        let mut xline = client(addr, 1).init().await.map_err(|e| e.error).unwrap();
+        assert_eq!(xline.identity().group, 24);
+        assert_eq!(xline.read_serial_number().await.unwrap().0, 4_000_123);
+    }
+
+    #[tokio::test]
+    async fn absent_devices_do_not_cost_a_reconnect() {
+        let (addr, accepted) = gateway().await;
+        let mut bus = client(addr, 2);
+        for address in 2..6 {
+            bus.set_address(address);
+            let error = bus.init().await.err().unwrap();
+            match &error.error {
+                ProtocolError::Transport(e) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+                other => panic!("unexpected error: {other}"),
+            }
+            bus = error.xline;
+            assert!(bus.transport_mut().is_connected());
+        }
+        bus.set_address(1);
+        bus.init().await.map_err(|e| e.error).unwrap();
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn a_closed_connection_is_reopened() {
+        let (addr, accepted) = gateway().await;
+        let mut xline = client(addr, 1).init().await.map_err(|e| e.error).unwrap();
+        xline.transport_mut().disconnect();
+        xline.read_serial_number().await.unwrap();
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,126 @@
+//! Helpers shared by the unit tests.
+
+use core::cell::{Cell, RefCell};
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use crate::KellerXLine;
+use crate::base::{Channels, Identity, XLineIO};
+use crate::emulator::XLineDevice;
+
+pub(crate) const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Runs a future that never waits on anything, such as a request to the
+/// emulator loopback.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    match future.as_mut().poll(&mut cx) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future is waiting on I/O"),
+    }
+}
+
+/// Identity of a firmware 5.`group` device built in week 19.03.
+pub(crate) fn identity(group: u8) -> Identity {
+    Identity {
+        class: 5,
+        group,
+        year: 19,
+        week: 3,
+        buffer: 10,
+        status: 0,
+    }
+}
+
+/// Client for an emulated device at address 1, already initialised.
+pub(crate) fn client(group: u8) -> KellerXLine<XLineDevice> {
+    let device = XLineDevice::new(1, identity(group), 4_000_123);
+    let xline = KellerXLine::new(device, TIMEOUT, 1).unwrap();
+    block_on(xline.init()).map_err(|e| e.error).unwrap()
+}
+
+/// Emulator whose raw P1 value follows `p1`, so a test can change the
+/// applied pressure while the client holds the device.
+pub(crate) struct Pressurised<'a> {
+    pub device: XLineDevice,
+    pub p1: &'a Cell<f32>,
+}
+
+impl XLineIO for Pressurised<'_> {
+    type Error = <XLineDevice as XLineIO>::Error;
+
+    async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Self::Error> {
+        self.device.set_raw(Channels::P1, Some(self.p1.get()));
+        self.device.write_all(buf, timeout).await
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), Self::Error> {
+        self.device.read_exact(buf, timeout).await
+    }
+
+    async fn clear_rx(&mut self) -> Result<(), Self::Error> {
+        self.device.clear_rx().await
+    }
+}
+
+/// Like [`client`], with P1 reading the pressure in `p1`.
+pub(crate) fn pressurised(group: u8, p1: &Cell<f32>) -> KellerXLine<Pressurised<'_>> {
+    let device = Pressurised {
+        device: XLineDevice::new(1, identity(group), 4_000_123),
+        p1,
+    };
+    let xline = KellerXLine::new(device, TIMEOUT, 1).unwrap();
+    block_on(xline.init()).map_err(|e| e.error).unwrap()
+}
+
+/// Emulator the test keeps hold of while a client talks to it, to change
+/// the device behind the client's back or count what reached it.
+pub(crate) struct Bus {
+    pub device: RefCell<XLineDevice>,
+    /// Requests of any kind, and F48 requests among them.
+    pub requests: Cell<usize>,
+    pub inits: Cell<usize>,
+}
+
+impl Bus {
+    pub fn new(group: u8) -> Self {
+        Self {
+            device: RefCell::new(XLineDevice::new(1, identity(group), 4_000_123)),
+            requests: Cell::new(0),
+            inits: Cell::new(0),
+        }
+    }
+
+    /// Initialised client on this bus; the F48 is not counted.
+    pub fn client(&self) -> KellerXLine<&Self> {
+        let xline = KellerXLine::new(self, TIMEOUT, 1).unwrap();
+        let xline = block_on(xline.init()).map_err(|e| e.error).unwrap();
+        self.requests.set(0);
+        self.inits.set(0);
+        xline
+    }
+}
+
+impl XLineIO for &Bus {
+    type Error = <XLineDevice as XLineIO>::Error;
+
+    async fn write_all(&mut self, buf: &[u8], timeout: Duration) -> Result<(), Self::Error> {
+        self.requests.set(self.requests.get() + 1);
+        if buf.get(1) == Some(&48) {
+            self.inits.set(self.inits.get() + 1);
+        }
+        // The emulator never waits, so the borrow ends before returning.
+        block_on(self.device.borrow_mut().write_all(buf, timeout))
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), Self::Error> {
+        block_on(self.device.borrow_mut().read_exact(buf, timeout))
+    }
+
+    async fn clear_rx(&mut self) -> Result<(), Self::Error> {
+        block_on(self.device.borrow_mut().clear_rx())
+    }
+}
@@ -0,0 +1,349 @@
+use crate::base::{Channels, XLineIO};
+use crate::{KellerXLine, XLineResult};
+
+/// Pascal per bar.
+const PA_PER_BAR: f32 = 100_000.0;
+/// Bar per psi.
+const BAR_PER_PSI: f32 = 0.068_947_57;
+/// Bar per inch of water (conventional, 4 °C).
+const BAR_PER_IN_H2O: f32 = 0.002_490_889;
+/// Bar per metre of water (conventional, g = 9.80665 m/s²).
+const BAR_PER_M_H2O: f32 = 0.098_066_5;
+
+/// Writes `value` followed by `unit`, honouring a requested precision.
+fn write_with_unit(f: &mut core::fmt::Formatter<'_>, value: f32, unit: &str) -> core::fmt::Result {
+    match f.precision() {
+        Some(precision) => write!(f, "{value:.precision$} {unit}"),
+        None => write!(f, "{value} {unit}"),
+    }
+}
+
+/// A pressure, kept in bar as the device reports it.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Pressure(f32);
+
+impl Pressure {
+    pub const fn from_bar(bar: f32) -> Self {
+        Self(bar)
+    }
+
+    pub fn from_mbar(mbar: f32) -> Self {
+        Self(mbar / 1000.0)
+    }
+
+    pub fn from_pa(pa: f32) -> Self {
+        Self(pa / PA_PER_BAR)
+    }
+
+    pub fn from_kpa(kpa: f32) -> Self {
+        Self(kpa * 1000.0 / PA_PER_BAR)
+    }
+
+    pub fn from_psi(psi: f32) -> Self {
+        Self(psi * BAR_PER_PSI)
+    }
+
+    pub fn from_in_h2o(in_h2o: f32) -> Self {
+        Self(in_h2o * BAR_PER_IN_H2O)
+    }
+
+    pub fn from_m_h2o(m_h2o: f32) -> Self {
+        Self(m_h2o * BAR_PER_M_H2O)
+    }
+
+    pub const fn bar(self) -> f32 {
+        self.0
+    }
+
+    pub fn mbar(self) -> f32 {
+        self.0 * 1000.0
+    }
+
+    pub fn pa(self) -> f32 {
+        self.0 * PA_PER_BAR
+    }
+
+    pub fn kpa(self) -> f32 {
+        self.0 * PA_PER_BAR / 1000.0
+    }
+
+    pub fn psi(self) -> f32 {
+        self.0 / BAR_PER_PSI
+    }
+
+    pub fn in_h2o(self) -> f32 {
+        self.0 / BAR_PER_IN_H2O
+    }
+
+    pub fn m_h2o(self) -> f32 {
+        self.0 / BAR_PER_M_H2O
+    }
+}
+
+impl core::fmt::Display for Pressure {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_with_unit(f, self.0, "bar")
+    }
+}
+
+/// A temperature, kept in °C as the device reports it.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Temperature(f32);
+
+impl Temperature {
+    pub const fn from_celsius(celsius: f32) -> Self {
+        Self(celsius)
+    }
+
+    pub fn from_fahrenheit(fahrenheit: f32) -> Self {
+        Self((fahrenheit - 32.0) * 5.0 / 9.0)
+    }
+
+    pub fn from_kelvin(kelvin: f32) -> Self {
+        Self(kelvin - 273.15)
+    }
+
+    pub const fn celsius(self) -> f32 {
+        self.0
+    }
+
+    pub fn fahrenheit(self) -> f32 {
+        self.0 * 9.0 / 5.0 + 32.0
+    }
+
+    pub fn kelvin(self) -> f32 {
+        self.0 + 273.15
+    }
+}
+
+impl core::fmt::Display for Temperature {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_with_unit(f, self.0, "°C")
+    }
+}
+
+/// An electrical conductivity, kept in mS/cm as the device reports it.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Conductivity(f32);
+
+impl Conductivity {
+    pub const fn from_ms_per_cm(ms_per_cm: f32) -> Self {
+        Self(ms_per_cm)
+    }
+
+    pub fn from_us_per_cm(us_per_cm: f32) -> Self {
+        Self(us_per_cm / 1000.0)
+    }
+
+    pub const fn ms_per_cm(self) -> f32 {
+        self.0
+    }
+
+    pub fn us_per_cm(self) -> f32 {
+        self.0 * 1000.0
+    }
+}
+
+impl core::fmt::Display for Conductivity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_with_unit(f, self.0, "mS/cm")
+    }
+}
+
+/// Channels that measure pressure.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PressureChannel {
+    P1,
+    P2,
+}
+
+/// Channels that measure temperature.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TemperatureChannel {
+    T,
+    TOB1,
+    TOB2,
+}
+
+/// Channels that measure conductivity (v5.21).
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConductivityChannel {
+    /// Temperature compensated.
+    ConTc,
+    ConRaw,
+}
+
+impl From<PressureChannel> for Channels {
+    fn from(channel: PressureChannel) -> Self {
+        match channel {
+            PressureChannel::P1 => Channels::P1,
+            PressureChannel::P2 => Channels::P2,
+        }
+    }
+}
+
+impl From<TemperatureChannel> for Channels {
+    fn from(channel: TemperatureChannel) -> Self {
+        match channel {
+            TemperatureChannel::T => Channels::T,
+            TemperatureChannel::TOB1 => Channels::TOB1,
+            TemperatureChannel::TOB2 => Channels::TOB2,
+        }
+    }
+}
+
+impl From<ConductivityChannel> for Channels {
+    fn from(channel: ConductivityChannel) -> Self {
+        match channel {
+            ConductivityChannel::ConTc => Channels::ConTc,
+            ConductivityChannel::ConRaw => Channels::ConRaw,
+        }
+    }
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    pub async fn read_pressure(
+        &mut self,
+        channel: PressureChannel,
+    ) -> XLineResult<Pressure, T::Error> {
+        Ok(Pressure::from_bar(
+            self.read_channel_value(channel.into()).await?,
+        ))
+    }
+
+    pub async fn read_temperature(
+        &mut self,
+        channel: TemperatureChannel,
+    ) -> XLineResult<Temperature, T::Error> {
+        Ok(Temperature::from_celsius(
+            self.read_channel_value(channel.into()).await?,
+        ))
+    }
+
+    pub async fn read_conductivity(
+        &mut self,
+        channel: ConductivityChannel,
+    ) -> XLineResult<Conductivity, T::Error> {
+        Ok(Conductivity::from_ms_per_cm(
+            self.read_channel_value(channel.into()).await?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Conductivity, Pressure, PressureChannel, Temperature};
+    use crate::base::Channels;
+    use crate::testing::{block_on, client};
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= b.abs().max(1.0) * 1e-6
+    }
+
+    #[test]
+    fn pressure_converts_from_bar() {
+        let bar = Pressure::from_bar(1.0);
+        assert_eq!(bar.mbar(), 1000.0);
+        assert_eq!(bar.pa(), 100_000.0);
+        assert_eq!(bar.kpa(), 100.0);
+        assert!(close(bar.psi(), 14.503_774));
+        assert!(close(bar.m_h2o(), 10.197_162));
+        assert!(close(bar.in_h2o(), 401.463_1));
+    }
+
+    #[test]
+    fn pressure_converts_to_bar() {
+        assert_eq!(Pressure::from_mbar(250.0).bar(), 0.25);
+        assert_eq!(Pressure::from_pa(101_325.0).bar(), 1.013_25);
+        assert_eq!(Pressure::from_kpa(50.0).bar(), 0.5);
+        assert!(close(Pressure::from_psi(1.0).bar(), 0.068_947_57));
+        assert!(close(Pressure::from_m_h2o(10.0).bar(), 0.980_665));
+        assert!(close(Pressure::from_in_h2o(1.0).bar(), 0.002_490_889));
+    }
+
+    #[test]
+    fn pressure_round_trips() {
+        for bar in [-1.0, 0.0, 0.001, 2.5, 300.0] {
+            let p = Pressure::from_bar(bar);
+            assert!(close(Pressure::from_mbar(p.mbar()).bar(), bar));
+            assert!(close(Pressure::from_pa(p.pa()).bar(), bar));
+            assert!(close(Pressure::from_kpa(p.kpa()).bar(), bar));
+            assert!(close(Pressure::from_psi(p.psi()).bar(), bar));
+            assert!(close(Pressure::from_m_h2o(p.m_h2o()).bar(), bar));
+            assert!(close(Pressure::from_in_h2o(p.in_h2o()).bar(), bar));
+        }
+    }
+
+    #[test]
+    fn temperature_converts() {
+        assert_eq!(Temperature::from_celsius(0.0).fahrenheit(), 32.0);
+        assert_eq!(Temperature::from_celsius(100.0).fahrenheit(), 212.0);
+        assert_eq!(Temperature::from_celsius(-40.0).fahrenheit(), -40.0);
+        assert_eq!(Temperature::from_celsius(0.0).kelvin(), 273.15);
+        assert_eq!(Temperature::from_fahrenheit(212.0).celsius(), 100.0);
+        assert_eq!(Temperature::from_kelvin(273.15).celsius(), 0.0);
+        assert!(close(Temperature::from_kelvin(0.0).celsius(), -273.15));
+    }
+
+    #[test]
+    fn temperature_round_trips() {
+        for celsius in [-40.0, -10.5, 0.0, 21.3, 85.0] {
+            let t = Temperature::from_celsius(celsius);
+            assert!(close(
+                Temperature::from_fahrenheit(t.fahrenheit()).celsius(),
+                celsius
+            ));
+            assert!(close(
+                Temperature::from_kelvin(t.kelvin()).celsius(),
+                celsius
+            ));
+        }
+    }
+
+    #[test]
+    fn conductivity_converts() {
+        assert_eq!(Conductivity::from_ms_per_cm(1.413).us_per_cm(), 1413.0);
+        assert_eq!(Conductivity::from_us_per_cm(500.0).ms_per_cm(), 0.5);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn display_honours_precision() {
+        use std::format;
+        assert_eq!(format!("{:.2}", Pressure::from_bar(1.0 / 3.0)), "0.33 bar");
+        assert_eq!(format!("{}", Temperature::from_celsius(21.5)), "21.5 °C");
+    }
+
+    #[test]
+    fn pressure_is_read_in_bar() {
+        let mut xline = client(24);
+        xline.transport_mut().set_raw(Channels::P1, Some(2.5));
+        let pressure = block_on(xline.read_pressure(PressureChannel::P1)).unwrap();
+        assert_eq!(pressure.bar(), 2.5);
+        assert_eq!(pressure.pa(), 250_000.0);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::base::{Coefficients, ConfigurationCommands};
+
+/// Whether writes are read back, and how the read value is compared.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum WriteVerify {
+    #[default]
+    Off,
+    /// The read-back must match bit for bit.
+    Exact,
+    /// Coefficients may differ by up to this much; configuration entries
+    /// must still match exactly.
+    Tolerance(f32),
+}
+
+impl WriteVerify {
+    pub fn is_enabled(self) -> bool {
+        self != WriteVerify::Off
+    }
+
+    /// Whether a coefficient read back as `actual` counts as `expected`.
+    pub fn matches(self, expected: f32, actual: f32) -> bool {
+        if expected.to_bits() == actual.to_bits() {
+            return true;
+        }
+        match self {
+            WriteVerify::Tolerance(tolerance) => (expected - actual).abs() <= tolerance,
+            WriteVerify::Off | WriteVerify::Exact => false,
+        }
+    }
+}
+
+/// A written (or read-back) value, as reported by
+/// [`crate::base::ProtocolError::VerifyMismatch`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WrittenValue {
+    Coefficient(Coefficients, f32),
+    Configuration(ConfigurationCommands, u8),
+}
+
+impl core::fmt::Display for WrittenValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            WrittenValue::Coefficient(c, value) => write!(f, "{c:?} = {value}"),
+            WrittenValue::Configuration(c, value) => write!(f, "{c:?} = {value}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WriteVerify, WrittenValue};
+    use crate::KellerXLine;
+    use crate::base::{Coefficients, ConfigurationCommands, ProtocolError};
+    use crate::emulator::XLineDevice;
+    use crate::fault::{Fault, FaultInjector};
+    use crate::testing::{TIMEOUT, block_on, identity};
+
+    /// Client verifying with `verify` whose first read-back is corrupted.
+    fn corrupted(verify: WriteVerify) -> KellerXLine<FaultInjector<XLineDevice>> {
+        let device = XLineDevice::new(1, identity(24), 4_000_123);
+        let mut injector = FaultInjector::new(device, 1);
+        // F48, the write, then its read-back.
+        injector.script(&[Fault::None, Fault::None, Fault::CorruptPayload]);
+        let xline = KellerXLine::new(injector, TIMEOUT, 1).unwrap();
+        let mut xline = block_on(xline.init()).map_err(|e| e.error).unwrap();
+        xline.set_write_verify(verify);
+        xline
+    }
+
+    #[test]
+    fn exact_needs_the_same_bits() {
+        let next = f32::from_bits(1.0f32.to_bits() + 1);
+        assert!(WriteVerify::Exact.matches(1.0, 1.0));
+        assert!(!WriteVerify::Exact.matches(1.0, next));
+        assert!(!WriteVerify::Exact.matches(0.0, -0.0));
+        assert!(WriteVerify::Exact.matches(f32::NAN, f32::NAN));
+        assert!(!WriteVerify::Exact.matches(f32::NAN, f32::from_bits(0x7fc0_1234)));
+        assert!(!WriteVerify::Off.matches(1.0, next));
+    }
+
+    #[test]
+    fn tolerance_allows_a_small_difference() {
+        let verify = WriteVerify::Tolerance(0.5);
+        assert!(verify.matches(1.0, 1.5));
+        assert!(verify.matches(1.0, 0.5));
+        assert!(!verify.matches(1.0, 1.75));
+        assert!(verify.matches(0.0, -0.0));
+        // NaN only matches its own bits, however loose the tolerance.
+        assert!(verify.matches(f32::NAN, f32::NAN));
+        assert!(!WriteVerify::Tolerance(f32::INFINITY).matches(f32::NAN, 1.0));
+        assert!(!WriteVerify::Tolerance(f32::INFINITY).matches(1.0, f32::NAN));
+    }
+
+    #[test]
+    fn corrupted_read_back_is_a_mismatch() {
+        let mut xline = corrupted(WriteVerify::Exact);
+        let result = block_on(xline.write_coefficent(Coefficients::GainFactorP1, 2.0));
+        // 2.0 is 0x4000_0000; the flipped exponent bit makes it 8.0.
+        assert!(matches!(
+            result,
+            Err(ProtocolError::VerifyMismatch {
+                expected: WrittenValue::Coefficient(Coefficients::GainFactorP1, 2.0),
+                actual: WrittenValue::Coefficient(Coefficients::GainFactorP1, 8.0),
+            })
+        ));
+        assert_eq!(xline.transport_mut().stats().corrupt_payload, 1);
+    }
+
+    #[test]
+    fn tolerance_accepts_a_read_back_within_range() {
+        let mut xline = corrupted(WriteVerify::Tolerance(6.0));
+        block_on(xline.write_coefficent(Coefficients::GainFactorP1, 2.0)).unwrap();
+    }
+
+    #[test]
+    fn configuration_must_match_exactly_even_with_a_tolerance() {
+        let mut xline = corrupted(WriteVerify::Tolerance(10.0));
+        assert!(matches!(
+            block_on(xline.write_configuration(ConfigurationCommands::Filter, 3)),
+            Err(ProtocolError::VerifyMismatch {
+                expected: WrittenValue::Configuration(ConfigurationCommands::Filter, 3),
+                actual: WrittenValue::Configuration(ConfigurationCommands::Filter, 2),
+            })
+        ));
+    }
+}
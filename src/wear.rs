@@ -0,0 +1,173 @@
+use crate::base::{KellerErrors, ProtocolError, XLineFrameError, XLineIO};
+use crate::{KellerXLine, XLineResult};
+
+/// Bus addresses a [`WearGuard`] keeps counters for.
+pub const WEAR_ADDRESSES: usize = 16;
+
+#[cfg(feature = "std")]
+type Counters = std::collections::HashMap<u8, WearCounters>;
+#[cfg(all(not(feature = "std"), feature = "embedded"))]
+type Counters = heapless::index_map::FnvIndexMap<u8, WearCounters, WEAR_ADDRESSES>;
+
+/// Write counters for one bus address.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct WearCounters {
+    /// Writes that went to the device, including those it failed to save.
+    pub written: u32,
+    /// Writes the device answered with [`KellerErrors::ErrorSavingValue`];
+    /// the EEPROM may have been touched all the same.
+    pub failed: u32,
+    /// Writes skipped because the device already held the value.
+    pub skipped: u32,
+}
+
+/// EEPROM wear protection for coefficient and configuration writes.
+///
+/// With `skip_redundant` every write first reads the entry and is skipped
+/// if the value is already there. With a `budget`, writes to one address
+/// beyond that many fail with [`crate::base::ProtocolError::WriteBudgetExceeded`].
+/// Counters are kept per bus address, so one handle can serve a whole bus,
+/// for up to [`WEAR_ADDRESSES`] addresses. Further addresses are not
+/// counted; with a budget their writes are refused, since their wear
+/// cannot be tracked.
+#[derive(Debug, Clone)]
+pub struct WearGuard {
+    pub skip_redundant: bool,
+    pub budget: Option<u32>,
+    counters: Counters,
+}
+
+impl Default for WearGuard {
+    fn default() -> Self {
+        Self::new(false, None)
+    }
+}
+
+impl WearGuard {
+    pub fn new(skip_redundant: bool, budget: Option<u32>) -> Self {
+        Self {
+            skip_redundant,
+            budget,
+            counters: Counters::new(),
+        }
+    }
+
+    pub fn counters(&self, address: u8) -> WearCounters {
+        self.counters.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Writes still allowed to `address`; `None` without a budget. An
+    /// address beyond the [`WEAR_ADDRESSES`] tracked ones has none left.
+    pub fn remaining(&self, address: u8) -> Option<u32> {
+        let budget = self.budget?;
+        if !self.counters.contains_key(&address) && self.counters.len() == WEAR_ADDRESSES {
+            return Some(0);
+        }
+        Some(budget.saturating_sub(self.counters(address).written))
+    }
+
+    /// Forgets what was counted for `address`, e.g. after a sensor swap.
+    pub fn reset(&mut self, address: u8) {
+        self.counters.remove(&address);
+    }
+
+    fn entry(&mut self, address: u8) -> Option<&mut WearCounters> {
+        if !self.counters.contains_key(&address) {
+            // Same limit with either map, so behaviour does not depend on
+            // the feature set.
+            if self.counters.len() == WEAR_ADDRESSES {
+                return None;
+            }
+            #[cfg(feature = "std")]
+            self.counters.insert(address, WearCounters::default());
+            #[cfg(all(not(feature = "std"), feature = "embedded"))]
+            self.counters
+                .insert(address, WearCounters::default())
+                .ok()?;
+        }
+        self.counters.get_mut(&address)
+    }
+
+    /// Counts a write request according to how the device answered it.
+    pub(crate) fn record_write<T, E>(&mut self, address: u8, result: &XLineResult<T, E>) {
+        let failed = match result {
+            Ok(_) => false,
+            Err(ProtocolError::FrameError(XLineFrameError::DeviceError(
+                KellerErrors::ErrorSavingValue,
+            ))) => true,
+            Err(_) => return,
+        };
+        if let Some(counters) = self.entry(address) {
+            counters.written += 1;
+            counters.failed += u32::from(failed);
+        }
+    }
+
+    pub(crate) fn record_skip(&mut self, address: u8) {
+        if let Some(counters) = self.entry(address) {
+            counters.skipped += 1;
+        }
+    }
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    pub fn wear_guard(&self) -> &WearGuard {
+        &self.wear
+    }
+
+    /// Configures redundant-write skipping and the write budget; see
+    /// [`WearGuard`].
+    pub fn wear_guard_mut(&mut self) -> &mut WearGuard {
+        &mut self.wear
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WEAR_ADDRESSES, WearCounters, WearGuard};
+    use crate::XLineResult;
+    use crate::base::{Coefficients, ProtocolError};
+    use crate::testing::{block_on, client};
+
+    #[test]
+    fn guard_is_small() {
+        assert!(size_of::<WearGuard>() < 512);
+    }
+
+    #[test]
+    fn failed_saves_count_as_writes() {
+        let mut xline = client(24);
+        xline.wear_guard_mut().budget = Some(2);
+        xline.transport_mut().set_fail_saves(true);
+        assert!(block_on(xline.write_coefficent(Coefficients::Free100, 1.0)).is_err());
+        assert_eq!(
+            xline.wear_guard().counters(1),
+            WearCounters {
+                written: 1,
+                failed: 1,
+                skipped: 0
+            }
+        );
+        xline.transport_mut().set_fail_saves(false);
+        block_on(xline.write_coefficent(Coefficients::Free100, 1.0)).unwrap();
+        assert!(matches!(
+            block_on(xline.write_coefficent(Coefficients::Free100, 2.0)),
+            Err(ProtocolError::WriteBudgetExceeded { limit: 2 })
+        ));
+    }
+
+    #[test]
+    fn untracked_addresses_have_no_budget_left() {
+        let mut guard = WearGuard::new(false, Some(5));
+        let ok: XLineResult<(), ()> = Ok(());
+        for address in 1..=WEAR_ADDRESSES as u8 {
+            guard.record_write(address, &ok);
+        }
+        assert_eq!(guard.remaining(1), Some(4));
+        assert_eq!(guard.remaining(200), Some(0));
+        guard.reset(1);
+        assert_eq!(guard.remaining(200), Some(5));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::KellerXLine;
+use crate::base::{Channels, Coefficients, ProtocolError, XLineIO, ZeroCommands};
+use crate::stability::{MAX_WINDOW, StabilityError, StableReading};
+
+/// Full windows [`KellerXLine::zero_stable`] reads before giving up.
+pub const ZERO_MAX_WINDOWS: usize = 10;
+
+/// Errors raised by [`KellerXLine::zero_stable`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug)]
+pub enum ZeroError<TE> {
+    Protocol(ProtocolError<TE>),
+    /// The readings did not settle; `std_dev` is the spread of the last
+    /// full window.
+    NotStable {
+        std_dev: f32,
+    },
+    /// The channel has no zero command or offset coefficient.
+    UnsupportedChannel(Channels),
+}
+
+impl<TE> From<ProtocolError<TE>> for ZeroError<TE> {
+    fn from(e: ProtocolError<TE>) -> Self {
+        ZeroError::Protocol(e)
+    }
+}
+
+impl<TE> From<StabilityError<TE>> for ZeroError<TE> {
+    fn from(e: StabilityError<TE>) -> Self {
+        match e {
+            StabilityError::Protocol(e) => ZeroError::Protocol(e),
+            StabilityError::NotStable { std_dev } => ZeroError::NotStable { std_dev },
+        }
+    }
+}
+
+/// What a stable zero did, with enough to undo it.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ZeroReport {
+    pub channel: Channels,
+    pub command: ZeroCommands,
+    /// Averaged reading the zero was taken at.
+    pub reading: StableReading,
+    /// Value the channel reads at that point after zeroing.
+    pub target: f32,
+    /// Offset coefficient before and after the zero.
+    pub previous_offset: f32,
+    pub new_offset: f32,
+}
+
+impl ZeroReport {
+    /// Change in the channel's reading caused by the zero.
+    pub fn shift(&self) -> f32 {
+        self.new_offset - self.previous_offset
+    }
+}
+
+impl<T: XLineIO> KellerXLine<T> {
+    /// Waits for `channel` to settle, then zeroes it so the settled reading
+    /// becomes 0.
+    ///
+    /// Readings are taken back to back until the last `window` of them have
+    /// a standard deviation of at most `max_std_dev`; after
+    /// [`ZERO_MAX_WINDOWS`] windows without settling nothing is written.
+    /// Keep the returned report to [`Self::undo_zero`] later.
+    pub async fn zero_stable(
+        &mut self,
+        channel: Channels,
+        window: usize,
+        max_std_dev: f32,
+    ) -> Result<ZeroReport, ZeroError<T::Error>> {
+        self.zero_stable_inner(channel, window, max_std_dev, None)
+            .await
+    }
+
+    /// Like [`Self::zero_stable`], but the settled reading becomes
+    /// `reference` (e.g. a known hydrostatic head) instead of 0.
+    pub async fn zero_stable_to(
+        &mut self,
+        channel: Channels,
+        window: usize,
+        max_std_dev: f32,
+        reference: f32,
+    ) -> Result<ZeroReport, ZeroError<T::Error>> {
+        self.zero_stable_inner(channel, window, max_std_dev, Some(reference))
+            .await
+    }
+
+    async fn zero_stable_inner(
+        &mut self,
+        channel: Channels,
+        window: usize,
+        max_std_dev: f32,
+        reference: Option<f32>,
+    ) -> Result<ZeroReport, ZeroError<T::Error>> {
+        let (Some(command), Some(offset)) =
+            (channel.set_zero_command(), self.offset_coefficient(channel))
+        else {
+            return Err(ZeroError::UnsupportedChannel(channel));
+        };
+        let previous_offset = self.read_coefficent(offset).await?;
+        let reading = self
+            .read_channel_stable(
+                channel,
+                window,
+                max_std_dev,
+                window.clamp(2, MAX_WINDOW) * ZERO_MAX_WINDOWS,
+            )
+            .await?;
+        match reference {
+            Some(value) => self.zero_with_value(command, value).await?,
+            None => self.zero(command).await?,
+        }
+        let new_offset = self.read_coefficent(offset).await?;
+        Ok(ZeroReport {
+            channel,
+            command,
+            reading,
+            target: reference.unwrap_or(0.0),
+            previous_offset,
+            new_offset,
+        })
+    }
+
+    /// Offset coefficient of `channel`, per the firmware variant if known.
+    fn offset_coefficient(&self, channel: Channels) -> Option<Coefficients> {
+        match self.firmware {
+            Some(firmware) => firmware.offset_coefficient(channel),
+            None => channel.offset_coefficient(),
+        }
+    }
+
+    /// Puts back the offset that was in place before `report`'s zero.
+    pub async fn undo_zero(&mut self, report: &ZeroReport) -> Result<(), ZeroError<T::Error>> {
+        let offset = self
+            .offset_coefficient(report.channel)
+            .ok_or(ZeroError::UnsupportedChannel(report.channel))?;
+        self.write_coefficent(offset, report.previous_offset)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZeroError;
+    use crate::base::{Channels, Coefficients, ZeroCommands};
+    use crate::testing::{block_on, client};
+
+    #[test]
+    fn zeroes_once_the_reading_is_stable() {
+        let mut xline = client(24);
+        let device = xline.transport_mut();
+        device.set_raw(Channels::P1, Some(1.0));
+        device.set_coefficient(Coefficients::PressureOffsetP1, Some(1.0));
+        device.set_coefficient(Coefficients::GainFactorP1, Some(2.0));
+        let report = block_on(xline.zero_stable(Channels::P1, 4, 0.01)).unwrap();
+        assert_eq!(report.command, ZeroCommands::SetZeroP1);
+        assert_eq!(report.reading.mean, 3.0);
+        assert_eq!((report.previous_offset, report.new_offset), (1.0, -2.0));
+        assert_eq!(report.shift(), -3.0);
+        assert_eq!(
+            block_on(xline.read_channel_value(Channels::P1)).unwrap(),
+            0.0
+        );
+
+        block_on(xline.undo_zero(&report)).unwrap();
+        assert_eq!(
+            block_on(xline.read_channel_value(Channels::P1)).unwrap(),
+            3.0
+        );
+    }
+
+    #[test]
+    fn zeroes_to_a_reference() {
+        let mut xline = client(24);
+        xline.transport_mut().set_raw(Channels::P1, Some(2.5));
+        let report = block_on(xline.zero_stable_to(Channels::P1, 4, 0.01, 1.0)).unwrap();
+        assert_eq!(report.new_offset, -1.5);
+        assert_eq!(
+            block_on(xline.read_channel_value(Channels::P1)).unwrap(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn t_has_no_offset_on_v5_20() {
+        let mut xline = client(20);
+        assert!(matches!(
+            block_on(xline.zero_stable(Channels::T, 4, 0.01)),
+            Err(ZeroError::UnsupportedChannel(Channels::T))
+        ));
+    }
+}